@@ -0,0 +1,29 @@
+#!/bin/sh
+# Build a release binary without the `dev_tools` feature and check that none of the dev command and
+# modal dev tool types are linked into it. Their type paths end up in the binary as soon as anything registers them.
+set -e
+cd "$(dirname "$0")/.."
+
+cargo build --release --no-default-features
+binary=target/release/bevy_dev_CLI_prototype
+
+commands=$(grep -rhoE 'impl (Reversible)?DevCommand for [A-Za-z0-9_]+' src | awk '{ print $NF }' | sort -u)
+tools=$(grep -rhoE 'impl ModalDevTool for [A-Za-z0-9_]+' src | awk '{ print $NF }' | sort -u)
+found=0
+for command in $commands; do
+    if grep -qaF "::${command}" "$binary"; then
+        echo "dev command still linked: $command"
+        found=1
+    fi
+done
+for tool in $tools; do
+    if grep -qaF "::${tool}" "$binary"; then
+        echo "dev tool still linked: $tool"
+        found=1
+    fi
+done
+
+if [ "$found" -ne 0 ]; then
+    exit 1
+fi
+echo "no dev commands or tools linked into $binary"
@@ -0,0 +1,87 @@
+use std::sync::{Arc, Mutex};
+
+use rustyline::completion::Completer;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Helper};
+
+/// A snapshot of everything that can be typed in the console, used to complete input
+/// without borrowing the toolbox.
+#[derive(Default, Clone)]
+pub struct CompletionTree {
+    /// Every callable path, such as `gold set`, with the names of its arguments.
+    pub entries: Vec<(String, Vec<String>)>,
+}
+
+impl CompletionTree {
+    /// Complete the last word of `line`.
+    ///
+    /// Returns the byte offset where the completed word starts and the candidates for it.
+    /// While the input names a group, the candidates are the next words of the paths in that group;
+    /// once it names a command, they are its `--arguments`.
+    pub fn complete(&self, line: &str) -> (usize, Vec<String>) {
+        let start = line
+            .rfind(char::is_whitespace)
+            .map(|idx| idx + 1)
+            .unwrap_or(0);
+        let partial = line[start..].to_lowercase();
+        let words = line[..start]
+            .split_whitespace()
+            .map(|word| word.to_lowercase())
+            .collect::<Vec<_>>();
+
+        // The longest path that the previous words start with
+        let command = (1..=words.len()).rev().find_map(|len| {
+            let path = words[..len].join(" ");
+            self.entries.iter().find(|(entry, _)| *entry == path)
+        });
+
+        let mut candidates = if let Some((_, arguments)) = command {
+            arguments
+                .iter()
+                .map(|argument| format!("--{}", argument))
+                .filter(|argument| argument.starts_with(&partial))
+                .collect::<Vec<_>>()
+        } else {
+            self.entries
+                .iter()
+                .filter_map(|(path, _)| {
+                    let segments = path.split(' ').collect::<Vec<_>>();
+                    let is_in_group = segments.len() > words.len()
+                        && segments.iter().zip(words.iter()).all(|(segment, word)| segment == word);
+                    is_in_group.then(|| segments[words.len()].to_string())
+                })
+                .filter(|segment| segment.starts_with(&partial))
+                .collect::<Vec<_>>()
+        };
+
+        candidates.sort();
+        candidates.dedup();
+        (start, candidates)
+    }
+}
+
+/// Rustyline helper that completes console input from a [`CompletionTree`].
+///
+/// The tree is shared so that it can be kept up to date while the editor runs on its own thread.
+#[derive(Default)]
+pub struct ConsoleHelper(pub Arc<Mutex<CompletionTree>>);
+
+impl Completer for ConsoleHelper {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(self.0.lock().unwrap().complete(&line[..pos]))
+    }
+}
+
+impl Hinter for ConsoleHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ConsoleHelper {}
+
+impl Validator for ConsoleHelper {}
+
+impl Helper for ConsoleHelper {}
@@ -0,0 +1,167 @@
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+use crate::dev_api::{DevToolParseError, PermissionLevel};
+use crate::events::{command_failed, current_source, CommandSource};
+use crate::CLIToolBox;
+
+/// Confirmations asked before running destructive commands, such as `snapshot load`.
+///
+/// A destructive line typed in a console is held back, and the console is asked `[y/N]`. The next line
+/// from the same console answers: `y` runs the held line, and anything else cancels it. A line other than
+/// `n` or an empty one then runs as usual.
+/// Lines ending with `--yes` run without asking, and so do lines from code, which cannot answer. Key bindings
+/// and JSON-RPC calls cannot answer either, and are refused unless they confirm up front, so destructive
+/// lines are only bound with `--yes`.
+/// Replayed lines ask like typed ones, and are answered by the next replayed line.
+#[derive(Resource, Default)]
+pub struct Confirmations {
+    /// Run destructive commands without asking, for sessions driven by scripts rather than people.
+    pub script_mode: bool,
+    /// The line waiting for an answer, by the source it came from.
+    pending: HashMap<CommandSource, PendingLine>,
+}
+
+struct PendingLine {
+    name: String,
+    line: String,
+    caller: PermissionLevel,
+}
+
+/// Remove the `--yes` flag ending a line, returning the line and whether the flag was there.
+///
+/// Only the last word is the flag, so arguments that happen to be `--yes` are kept.
+pub fn strip_yes(s: &str) -> (String, bool) {
+    let trimmed = s.trim_end();
+    match trimmed.strip_suffix("--yes") {
+        Some(line) if line.is_empty() || line.ends_with(char::is_whitespace) => (line.trim_end().to_string(), true),
+        _ => (s.to_string(), false),
+    }
+}
+
+impl CLIToolBox {
+    /// Answer the confirmation the current source was asked for, if any. Returns whether the line was only an answer.
+    ///
+    /// Lines other than `y` or `yes` cancel the held line, and the ones that are not a plain `n`, `no` or empty
+    /// answer are not consumed, so the caller handles them as usual.
+    pub fn answer_confirmation(&self, s: &str, world: &mut World) -> bool {
+        let source = current_source(world);
+        let Some(pending) = world.get_resource_mut::<Confirmations>().and_then(|mut confirmations| confirmations.pending.remove(&source)) else {
+            return false;
+        };
+        match s.trim().to_lowercase().as_str() {
+            "y" | "yes" => {
+                self.parse_input(&format!("{} --yes", pending.line), pending.caller, world);
+                true
+            },
+            "" | "n" | "no" => {
+                info!("Cancelled {}", pending.name);
+                true
+            },
+            _ => {
+                info!("Cancelled {}", pending.name);
+                false
+            },
+        }
+    }
+
+    /// Whether a line runs a destructive command.
+    pub fn is_destructive(&self, line: &str) -> bool {
+        let (line, _) = strip_yes(line);
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        if words.first().is_some_and(|name| name.eq_ignore_ascii_case("snapshot")) && words.get(1) == Some(&"load") {
+            return true;
+        }
+        matches!(self.find_command(&words), Ok(Some((qualified_path, _))) if self.metadatas[&qualified_path].destructive)
+    }
+
+    /// Whether the destructive command `name` can run now. Otherwise, hold back its line until the current source
+    /// confirms it, and return `false`.
+    pub fn confirm(&self, name: &str, line: &str, caller: PermissionLevel, yes: bool, world: &mut World) -> bool {
+        let source = current_source(world);
+        let mut confirmations = world.get_resource_or_insert_with(Confirmations::default);
+        let interactive = matches!(source, CommandSource::Frontend(_) | CommandSource::Replay);
+        if yes || confirmations.script_mode {
+            return true;
+        }
+        if matches!(source, CommandSource::Remote(_) | CommandSource::Keybinding) {
+            let err = DevToolParseError::NotConfirmed(name.to_string());
+            error!("{}, add --yes to run it", err);
+            command_failed(world, name, err);
+            return false;
+        }
+        if !interactive {
+            return true;
+        }
+        confirmations.pending.insert(source, PendingLine {
+            name: name.to_string(),
+            line: line.to_string(),
+            caller,
+        });
+        info!("'{}' is destructive, run it anyway? [y/N]", name);
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frontend::FrontendId;
+    use crate::harness::DevConsoleTestHarness;
+    use crate::keybinds::Keybindings;
+    use crate::log_capture::LogLine;
+
+    fn run_from(harness: &mut DevConsoleTestHarness, line: &str, source: CommandSource) -> Vec<String> {
+        let output: Vec<LogLine> = harness.world_mut().resource_scope(|world, toolbox: Mut<CLIToolBox>| {
+            toolbox.execute_from(line, PermissionLevel::Admin, source, world)
+        });
+        output.into_iter().map(|line| line.message).collect()
+    }
+
+    #[test]
+    fn only_a_trailing_yes_is_stripped() {
+        assert_eq!(strip_yes("snapshot load a --yes"), ("snapshot load a".to_string(), true));
+        assert_eq!(strip_yes("snapshot load a --yes  "), ("snapshot load a".to_string(), true));
+        assert_eq!(strip_yes("say --yes  twice"), ("say --yes  twice".to_string(), false));
+        assert_eq!(strip_yes("say not--yes"), ("say not--yes".to_string(), false));
+        assert_eq!(strip_yes("--yes"), (String::new(), true));
+    }
+
+    #[test]
+    fn only_y_confirms() {
+        let mut harness = DevConsoleTestHarness::new();
+        let console = CommandSource::Frontend(FrontendId(0));
+        let output = run_from(&mut harness, "snapshot load missing", console);
+        assert!(output.iter().any(|line| line.contains("run it anyway? [y/N]")), "{:?}", output);
+
+        let output = run_from(&mut harness, "n", console);
+        assert_eq!(output, vec!["Cancelled snapshot load".to_string()]);
+        assert!(harness.world().resource::<Confirmations>().pending.is_empty());
+
+        // Another line cancels too, and then runs
+        run_from(&mut harness, "snapshot load missing", console);
+        let output = run_from(&mut harness, "sv_cheats", console);
+        assert_eq!(output[0], "Cancelled snapshot load");
+        assert!(output[1..].iter().any(|line| line.starts_with("sv_cheats")), "{:?}", output);
+        assert!(harness.world().resource::<Confirmations>().pending.is_empty());
+
+        run_from(&mut harness, "snapshot load missing", console);
+        let output = run_from(&mut harness, "y", console);
+        assert!(output.iter().any(|line| line.contains("no snapshot named missing")), "{:?}", output);
+    }
+
+    #[test]
+    fn key_bindings_need_yes() {
+        let mut harness = DevConsoleTestHarness::new();
+        let output = harness.run("bind f5 snapshot load quick");
+        assert!(output.has_error("bind it with --yes"), "{:?}", output);
+        assert!(harness.run("bind f5 snapshot load quick --yes").is_ok());
+        let bound = harness.world().resource::<Keybindings>().iter().map(|(_, line)| line.to_string()).collect::<Vec<_>>();
+        assert_eq!(bound, vec!["snapshot load quick --yes".to_string()]);
+
+        // Bindings made in code are refused when they run
+        let output = run_from(&mut harness, "snapshot load quick", CommandSource::Keybinding);
+        assert!(output.iter().any(|line| line.contains("was not confirmed")), "{:?}", output);
+        assert!(harness.world().resource::<Confirmations>().pending.is_empty());
+    }
+}
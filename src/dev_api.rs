@@ -6,10 +6,140 @@ use bevy::reflect::TypeInfo;
 use bevy::{prelude::*, reflect::GetTypeRegistration};
 
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum DevToolParseError {
     InvalidName,
     InvalidToolData,
+    /// Required arguments that were not supplied, by field name.
+    MissingArguments(Vec<String>),
+    /// A command or tool is already registered under this qualified name.
+    NameCollision(String),
+    /// A short name matches several commands or tools, listed by qualified name.
+    AmbiguousName(Vec<String>),
+    /// No command or tool has this name, with the closest known names.
+    UnknownCommand { name: String, suggestions: Vec<String> },
+    /// The command or tool has no field with this name, with the closest field names.
+    UnknownField { name: String, suggestions: Vec<String> },
+    /// The tool has no preset with this name, with the closest preset names.
+    UnknownPreset { tool: String, name: String, suggestions: Vec<String> },
+    /// A JSON argument could not be deserialized into its field.
+    InvalidJson(String),
+    /// The caller is not allowed to run this command, which needs the given level.
+    PermissionDenied { name: String, required: PermissionLevel },
+    /// This command is a cheat, and cheats are disabled.
+    CheatsDisabled(String),
+    /// A [`DevCommandMiddleware`] refused to run this command, for the given reason.
+    Vetoed { name: String, reason: String },
+    /// This command is destructive, and the caller cannot be asked to confirm it and did not confirm it up front.
+    NotConfirmed(String),
+    /// This command runs on the server, and could not be sent to it.
+    NotSent(String),
+}
+
+impl std::fmt::Display for DevToolParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DevToolParseError::InvalidName => write!(f, "invalid name"),
+            DevToolParseError::InvalidToolData => write!(f, "invalid tool data"),
+            DevToolParseError::MissingArguments(missing) => write!(f, "missing required arguments: {}", missing.join(", ")),
+            DevToolParseError::NameCollision(name) => write!(f, "'{}' is already registered", name),
+            DevToolParseError::AmbiguousName(candidates) => write!(f, "ambiguous name, could be any of: {}", candidates.join(", ")),
+            DevToolParseError::UnknownCommand { name, suggestions } => {
+                write!(f, "unknown command '{}'", name)?;
+                write_suggestions(f, suggestions)
+            },
+            DevToolParseError::UnknownField { name, suggestions } => {
+                write!(f, "unknown field '{}'", name)?;
+                write_suggestions(f, suggestions)
+            },
+            DevToolParseError::UnknownPreset { tool, name, suggestions } => {
+                write!(f, "{} has no preset '{}'", tool, name)?;
+                write_suggestions(f, suggestions)
+            },
+            DevToolParseError::InvalidJson(err) => write!(f, "invalid JSON argument: {}", err),
+            DevToolParseError::PermissionDenied { name, required } => write!(f, "permission denied: '{}' needs the {} level", name, required),
+            DevToolParseError::CheatsDisabled(name) => write!(f, "'{}' is a cheat, and cheats are disabled (sv_cheats 0)", name),
+            DevToolParseError::Vetoed { name, reason } => write!(f, "'{}' was vetoed: {}", name, reason),
+            DevToolParseError::NotConfirmed(name) => write!(f, "'{}' is destructive and was not confirmed", name),
+            DevToolParseError::NotSent(name) => write!(f, "'{}' could not be sent to the server", name),
+        }
+    }
+}
+
+/// Append `; did you mean 'a' or 'b'?` when there are suggestions.
+fn write_suggestions(f: &mut std::fmt::Formatter<'_>, suggestions: &[String]) -> std::fmt::Result {
+    if suggestions.is_empty() {
+        return Ok(());
+    }
+    let quoted = suggestions.iter().map(|suggestion| format!("'{}'", suggestion)).collect::<Vec<_>>();
+    write!(f, "; did you mean {}?", quoted.join(" or "))
+}
+
+/// What a caller is allowed to run, from the least to the most trusted.
+///
+/// Each console frontend runs its commands at a level, and a command can only be run by callers of at least its level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PermissionLevel {
+    /// Commands that only read the state of the app.
+    ReadOnly,
+    /// Commands that change the game in ways players could exploit. They also need cheats to be enabled.
+    Cheat,
+    /// Commands that control the app and the console itself, such as `exit`.
+    Admin,
+}
+
+impl std::fmt::Display for PermissionLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PermissionLevel::ReadOnly => write!(f, "read-only"),
+            PermissionLevel::Cheat => write!(f, "cheat"),
+            PermissionLevel::Admin => write!(f, "admin"),
+        }
+    }
+}
+
+impl FromStr for PermissionLevel {
+    type Err = DevToolParseError;
+    fn from_str(s: &str) -> Result<Self, DevToolParseError> {
+        match s.to_lowercase().as_str() {
+            "read-only" | "readonly" => Ok(PermissionLevel::ReadOnly),
+            "cheat" => Ok(PermissionLevel::Cheat),
+            "admin" => Ok(PermissionLevel::Admin),
+            _ => Err(DevToolParseError::InvalidName),
+        }
+    }
+}
+
+/// Where a dev command runs in a multiplayer game, see [`CommandRouting`](crate::routing::CommandRouting).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RunsOn {
+    /// On the peer the command was issued on, such as commands moving the local camera.
+    Client,
+    /// On the server, which owns the state of the game. Clients send these commands to the server instead of applying them.
+    Server,
+    /// On the peer the command was issued on, and on the server.
+    Both,
+}
+
+impl std::fmt::Display for RunsOn {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RunsOn::Client => write!(f, "client"),
+            RunsOn::Server => write!(f, "server"),
+            RunsOn::Both => write!(f, "both"),
+        }
+    }
+}
+
+/// How an argument of a dev command has to be supplied on the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgRequirement {
+    /// The argument must always be supplied.
+    Required,
+    /// The argument can be left out, in which case the value from `Default` is kept.
+    Optional,
+    /// The argument can be left out, in which case this string is parsed as its value.
+    Default(&'static str),
 }
 
 /// Modal dev tools are used by developers to inspect their application in a toggleable way,
@@ -29,6 +159,11 @@ pub trait ModalDevTool: Resource + Reflect + FromReflect + GetTypeRegistration +
         None
     }
 
+    /// The level needed to reconfigure this tool. Tools are cheats unless they say otherwise.
+    fn permission() -> PermissionLevel {
+        PermissionLevel::Cheat
+    }
+
     /// The metadata for this modal dev tool.
     fn metadata() -> DevToolMetaData {
         DevToolMetaData {
@@ -37,7 +172,9 @@ pub trait ModalDevTool: Resource + Reflect + FromReflect + GetTypeRegistration +
             type_info: Self::get_type_registration().type_info(),
             // A function pointer, based on the std::str::from_str method
             from_str_fn: |s| <Self as FromStr>::from_str(s).map(|x| Box::new(x) as Box<dyn Reflect>),
-            short_description: Self::short_description()
+            create_default_fn: || Box::new(Self::default()),
+            short_description: Self::short_description(),
+            permission: Self::permission(),
         }
     }
 
@@ -72,7 +209,38 @@ pub struct DevToolMetaData {
     pub type_id: TypeId,
     pub type_info: &'static TypeInfo,
     pub from_str_fn: fn(&str) -> Result<Box<dyn Reflect>, DevToolParseError>,
-    pub short_description: Option<&'static str>
+    pub create_default_fn: fn() -> Box<dyn Reflect>,
+    pub short_description: Option<&'static str>,
+    pub permission: PermissionLevel,
+}
+
+impl DevToolMetaData {
+    /// The name of this tool qualified by its module, such as `mygame::camera::devflycamera`.
+    pub fn qualified_name(&self) -> String {
+        qualify(self.type_info, &self.name.to_lowercase())
+    }
+
+    /// The names of the configurable fields of this tool.
+    pub fn field_names(&self) -> Vec<String> {
+        field_names(self.type_info)
+    }
+}
+
+/// Prefix a command path with the module path of its type, if it has one.
+fn qualify(type_info: &TypeInfo, path: &str) -> String {
+    match type_info.type_path_table().module_path() {
+        Some(module_path) => format!("{}::{}", module_path.to_lowercase(), path),
+        None => path.to_string(),
+    }
+}
+
+/// The names of the fields described by `type_info`, using their index for unnamed fields.
+pub fn field_names(type_info: &TypeInfo) -> Vec<String> {
+    match type_info {
+        TypeInfo::Struct(info) => info.iter().map(|field| field.name().to_string()).collect(),
+        TypeInfo::TupleStruct(info) => (0..info.field_len()).map(|idx| idx.to_string()).collect(),
+        _ => Vec::new(),
+    }
 }
 
 /// Dev commands are used by developers to modify the `World` in order to easily debug and test their application.
@@ -89,6 +257,30 @@ pub trait DevCommand: bevy::ecs::world::Command + Reflect + FromReflect + GetTyp
 
     fn short_description() -> Option<&'static str>;
 
+    /// How each argument of this command has to be supplied, by field name.
+    ///
+    /// Fields that are not listed here are optional.
+    fn arguments() -> Vec<(&'static str, ArgRequirement)> {
+        Vec::new()
+    }
+
+    /// The level needed to run this command. Commands are cheats unless they say otherwise.
+    fn permission() -> PermissionLevel {
+        PermissionLevel::Cheat
+    }
+
+    /// Where this command runs in a multiplayer game. Commands run where they are issued unless they say otherwise.
+    fn runs_on() -> RunsOn {
+        RunsOn::Client
+    }
+
+    /// Whether running this command by accident would lose work, such as despawning every entity.
+    ///
+    /// Destructive commands typed in a console ask for confirmation first, unless `--yes` is given.
+    fn destructive() -> bool {
+        false
+    }
+
     /// The metadata for this dev command.
     fn metadata() -> DevCommandMetadata {
         DevCommandMetadata {
@@ -99,11 +291,89 @@ pub trait DevCommand: bevy::ecs::world::Command + Reflect + FromReflect + GetTyp
             from_str_fn: |s| <Self as FromStr>::from_str(s).map(|x| Box::new(x) as Box<dyn Reflect>),
             create_default_fn: || Box::new(Self::default()),
             add_self_to_commands_fn: |commands, reflected_self| commands.add(<Self as FromReflect>::from_reflect(reflected_self).unwrap()),
-            short_description: Self::short_description()
+            short_description: Self::short_description(),
+            arguments: Self::arguments(),
+            path: Self::name().to_lowercase(),
+            inverse_fn: None,
+            permission: Self::permission(),
+            runs_on: Self::runs_on(),
+            destructive: Self::destructive(),
         }
     }
 }
 
+/// Behavior added around every dev command the toolbox runs, such as logging cheats or rate-limiting remote calls.
+///
+/// Middlewares are registered with [`CLIToolBox::add_middleware`](crate::CLIToolBox::add_middleware) and run in
+/// the order they were added. Their `before` hooks see each parsed command before it is applied or sent to the
+/// server, and can rewrite its fields or veto it. Their `after` hooks then see the outcome, in the reverse order.
+pub trait DevCommandMiddleware: Send + Sync + 'static {
+    /// Called before a command runs, with the command as rewritten by the middlewares before this one.
+    ///
+    /// Change the fields of `command` to rewrite it, or return an error to veto it.
+    fn before(&self, _command: &mut dyn Reflect, _metadata: &DevCommandMetadata, _world: &mut World) -> Result<(), DevToolParseError> {
+        Ok(())
+    }
+
+    /// Called once the command ran, was sent to the server, was vetoed or could not run.
+    fn after(&self, _command: &dyn Reflect, _metadata: &DevCommandMetadata, _outcome: &CommandOutcome, _world: &mut World) {}
+}
+
+/// What happened to a command, as seen by [`DevCommandMiddleware::after`].
+#[derive(Debug)]
+pub enum CommandOutcome {
+    /// The command was applied to the world.
+    Applied,
+    /// The command was handed to the server instead of being applied here.
+    Sent,
+    /// A middleware vetoed the command before it ran.
+    Vetoed(DevToolParseError),
+    /// The command ran nowhere, such as when it could not be sent to the server.
+    Failed(DevToolParseError),
+}
+
+/// Work started by a dev command that runs across several frames, such as a benchmark or waiting for an asset to load.
+///
+/// `DevCommand::apply` has to finish within one frame, so long-running commands start a job with
+/// [`DevJobs::start`](crate::jobs::DevJobs::start) instead. The job is polled once per frame until it is done,
+/// its progress is reported to the console, and it can be listed with `jobs` and cancelled with `cancel <id>`.
+pub trait DevJob: Send + Sync + 'static {
+    /// Do the work of one frame.
+    fn poll(&mut self, world: &mut World) -> JobStatus;
+
+    /// Clean up after the job is cancelled before it is done.
+    fn cancel(&mut self, _world: &mut World) {}
+}
+
+/// Whether a [`DevJob`] needs more frames.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JobStatus {
+    /// The job needs more frames. Holds the fraction of the work done so far, from 0 to 1, when it is known.
+    Running(Option<f32>),
+    Done,
+}
+
+/// Dev commands that can be undone.
+///
+/// Before a reversible command is applied, the toolbox asks it for the command that restores the
+/// current state, and runs that command on `undo`.
+pub trait ReversibleDevCommand: DevCommand {
+    /// The command undoing this one, given the world before this one is applied.
+    fn inverse(&self, world: &World) -> Self;
+
+    /// The metadata for this dev command, including how to create its inverse.
+    fn reversible_metadata() -> DevCommandMetadata {
+        let mut metadata = Self::metadata();
+        metadata.inverse_fn = Some(|reflected_self, world| {
+            Box::new(<Self as FromReflect>::from_reflect(reflected_self).unwrap().inverse(world))
+        });
+        metadata
+    }
+}
+
+/// Creates the command undoing a reversible command, from the state of the world before it runs.
+pub type InverseFn = fn(reflected_self: &dyn Reflect, world: &World) -> Box<dyn Reflect>;
+
 pub struct DevCommandMetadata {
     pub name: &'static str,
     pub type_id: TypeId,
@@ -111,5 +381,54 @@ pub struct DevCommandMetadata {
     pub from_str_fn: fn(&str) -> Result<Box<dyn Reflect>, DevToolParseError>,
     pub create_default_fn: fn() -> Box<dyn Reflect>,
     pub add_self_to_commands_fn: fn(commands: &mut Commands, reflected_self: &dyn Reflect),
-    pub short_description: Option<&'static str>
+    pub short_description: Option<&'static str>,
+    pub arguments: Vec<(&'static str, ArgRequirement)>,
+    /// The words typed to call this command, such as `gold set`.
+    pub path: String,
+    /// Creates the command undoing this one, for [`ReversibleDevCommand`]s.
+    pub inverse_fn: Option<InverseFn>,
+    pub permission: PermissionLevel,
+    pub runs_on: RunsOn,
+    pub destructive: bool,
+}
+
+impl DevCommandMetadata {
+    /// How the argument for the given field has to be supplied.
+    pub fn requirement(&self, field: &str) -> ArgRequirement {
+        self.arguments
+            .iter()
+            .find(|(name, _)| *name == field)
+            .map(|(_, requirement)| *requirement)
+            .unwrap_or(ArgRequirement::Optional)
+    }
+
+    /// The path of this command qualified by its module, such as `mygame::economy::gold set`.
+    pub fn qualified_path(&self) -> String {
+        qualify(self.type_info, &self.path)
+    }
+
+    /// The names of the arguments of this command, in positional order.
+    pub fn argument_names(&self) -> Vec<String> {
+        field_names(self.type_info)
+    }
+
+    /// A one line usage string, such as `setgold <amount>`.
+    ///
+    /// Required arguments are shown in angle brackets, optional ones in square brackets.
+    pub fn usage(&self) -> String {
+        let mut usage = self.path.clone();
+        for name in self.argument_names() {
+            let argument = match self.requirement(&name) {
+                ArgRequirement::Required => format!("<{}>", name),
+                ArgRequirement::Optional => format!("[{}]", name),
+                ArgRequirement::Default(value) => format!("[{}={}]", name, value),
+            };
+            usage.push(' ');
+            usage.push_str(&argument);
+        }
+        if self.destructive {
+            usage.push_str(" [--yes]");
+        }
+        usage
+    }
 }
\ No newline at end of file
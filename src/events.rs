@@ -0,0 +1,100 @@
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+use crate::dev_api::DevToolParseError;
+use crate::frontend::FrontendId;
+use crate::remote::ConnectionId;
+use crate::routing::PeerId;
+
+/// Where a dev command or tool change came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CommandSource {
+    /// A line typed in a console frontend.
+    Frontend(FrontendId),
+    /// A JSON-RPC request of a remote console connection.
+    Remote(ConnectionId),
+    /// A command a client sent to this server.
+    Peer(PeerId),
+    /// A key chord bound with `bind`.
+    Keybinding,
+    /// A line of a replayed recording.
+    Replay,
+    /// Code calling the toolbox directly, such as tests.
+    Code,
+}
+
+/// Sent when a line is parsed into a dev command, before it runs.
+#[derive(Event)]
+pub struct DevCommandParsed {
+    /// The qualified path of the command.
+    pub path: String,
+    pub command: Box<dyn Reflect>,
+    pub source: CommandSource,
+}
+
+/// Sent when a dev command is applied to the world.
+///
+/// Commands that a client sends to the server are only applied, and reported, on the server.
+#[derive(Event)]
+pub struct DevCommandExecuted {
+    /// The qualified path of the command.
+    pub path: String,
+    pub command: Box<dyn Reflect>,
+    pub source: CommandSource,
+}
+
+/// Sent when a line names a dev command or tool that cannot run, because it does not exist,
+/// its arguments do not parse or the caller is not allowed to run it.
+#[derive(Event)]
+pub struct DevCommandFailed {
+    /// The name of the command or tool, as typed.
+    pub name: String,
+    pub error: DevToolParseError,
+    pub source: CommandSource,
+}
+
+/// Sent when fields of a modal dev tool are changed by the console, including by `undo`, presets and `tools reset`.
+#[derive(Event)]
+pub struct ToolPatched {
+    /// The qualified name of the tool.
+    pub tool: String,
+    /// The new values of the changed fields, by field name.
+    pub values: HashMap<String, Box<dyn Reflect>>,
+    pub source: CommandSource,
+}
+
+/// Resource holding the source of the line being run, while it runs.
+#[derive(Resource)]
+struct CurrentCommandSource(CommandSource);
+
+/// The source of the line being run, or [`CommandSource::Code`] outside of one.
+pub fn current_source(world: &World) -> CommandSource {
+    world.get_resource::<CurrentCommandSource>().map_or(CommandSource::Code, |source| source.0)
+}
+
+/// Run `f` with `source` as the [`current_source`].
+pub fn with_source<R>(world: &mut World, source: CommandSource, f: impl FnOnce(&mut World) -> R) -> R {
+    let previous = world.remove_resource::<CurrentCommandSource>();
+    world.insert_resource(CurrentCommandSource(source));
+    let result = f(world);
+    match previous {
+        Some(previous) => world.insert_resource(previous),
+        None => {
+            world.remove_resource::<CurrentCommandSource>();
+        },
+    }
+    result
+}
+
+/// Send a lifecycle event, when the app registered it.
+pub fn send_lifecycle_event<E: Event>(world: &mut World, event: E) {
+    if let Some(mut events) = world.get_resource_mut::<Events<E>>() {
+        events.send(event);
+    }
+}
+
+/// Send a [`DevCommandFailed`] for the line being run.
+pub fn command_failed(world: &mut World, name: &str, error: DevToolParseError) {
+    let source = current_source(world);
+    send_lifecycle_event(world, DevCommandFailed { name: name.to_string(), error, source });
+}
@@ -0,0 +1,223 @@
+use bevy::ecs::component::Tick;
+use bevy::input::InputSystem;
+use bevy::log::Level;
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+
+use crate::completion::CompletionTree;
+use crate::confirm::Confirmations;
+use crate::dev_api::PermissionLevel;
+use crate::events::{CommandSource, DevCommandExecuted, DevCommandFailed, DevCommandParsed, ToolPatched};
+use crate::jobs::DevJobsPlugin;
+use crate::keybinds::{run_keybindings, Keybindings};
+use crate::permissions::Cheats;
+use crate::presets::ToolPresets;
+use crate::replay::ConsoleReplayPlugin;
+use crate::routing::CommandRoutingPlugin;
+use crate::CLIToolBox;
+
+/// A place where dev commands are typed and their output is shown, such as a terminal,
+/// the in-game overlay, a network socket or a test harness.
+///
+/// Every frontend in [`DevConsoleFrontends`] is polled once per frame: the lines it returns from
+/// [`read_input`](DevConsoleFrontend::read_input) are run through the [`CLIToolBox`], and the output
+/// of each line is sent back to the frontend that issued it.
+pub trait DevConsoleFrontend: Send + Sync + 'static {
+    /// The lines submitted since the last call, oldest first.
+    fn read_input(&mut self) -> Vec<String>;
+
+    /// Show a line of output from a command issued by this frontend.
+    fn write_output(&mut self, line: &str);
+
+    /// Show an error from a command issued by this frontend.
+    fn report_error(&mut self, error: &str);
+
+    /// Called after all of the output of one input line has been written,
+    /// for frontends that group output by the line that produced it.
+    fn finish_command(&mut self) {}
+
+    /// Receive the current set of completions, called before input is read when the frontend is new
+    /// and whenever commands, tools or presets change.
+    fn update_completions(&mut self, _completions: &CompletionTree) {}
+
+    /// The level the lines of this frontend run at. Local frontends are trusted with every command.
+    fn permission(&self) -> PermissionLevel {
+        PermissionLevel::Admin
+    }
+}
+
+/// Identifies a frontend in [`DevConsoleFrontends`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FrontendId(pub usize);
+
+/// Resource holding every active console frontend.
+#[derive(Resource, Default)]
+pub struct DevConsoleFrontends {
+    frontends: Vec<(FrontendId, Box<dyn DevConsoleFrontend>)>,
+    next_id: usize,
+}
+
+impl DevConsoleFrontends {
+    pub fn add(&mut self, frontend: impl DevConsoleFrontend) -> FrontendId {
+        let id = FrontendId(self.next_id);
+        self.next_id += 1;
+        self.frontends.push((id, Box::new(frontend)));
+        id
+    }
+}
+
+/// The systems that run console input, in [`PreUpdate`] after the input is read.
+///
+/// Replayed commands run before the lines typed in the same frame, and every command runs before the
+/// game systems in [`Update`], so that a replay sees the same state as the recording.
+#[derive(SystemSet, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DevConsoleSet {
+    /// Runs the commands of the active replay, see [`ConsoleReplayPlugin`].
+    Replay,
+    /// Runs the lines of the frontends and of the keybindings, the commands received from clients and the JSON-RPC requests of remote consoles.
+    Dispatch,
+}
+
+/// Resource holding the completions sent to the frontends, rebuilt when the [`CLIToolBox`] or the [`ToolPresets`] change.
+#[derive(Resource, Default)]
+struct CompletionCache {
+    tree: CompletionTree,
+    /// When the toolbox and the presets last changed, as of the last rebuild.
+    built_from: Option<(Tick, Option<Tick>)>,
+    /// The frontends that have the current completions.
+    sent: HashSet<FrontendId>,
+}
+
+/// Runs the input of every [`DevConsoleFrontend`] through the [`CLIToolBox`] each frame,
+/// runs the command lines bound to key chords in [`Keybindings`],
+/// records and replays console sessions with the [`ConsoleReplayPlugin`],
+/// runs long-running commands with the [`DevJobsPlugin`],
+/// and sends commands to the server of a multiplayer game with the [`CommandRoutingPlugin`].
+///
+/// Systems can follow what the console runs by reading the [`DevCommandParsed`], [`DevCommandExecuted`],
+/// [`DevCommandFailed`] and [`ToolPatched`] events.
+///
+/// Without the `dev_tools` feature, the console plugins add no systems and read no input.
+#[derive(Default)]
+pub struct DevConsolePlugin;
+
+impl Plugin for DevConsolePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DevConsoleFrontends>()
+            .init_resource::<ToolPresets>()
+            .init_resource::<Keybindings>()
+            .init_resource::<Cheats>()
+            .init_resource::<Confirmations>()
+            .init_resource::<CompletionCache>()
+            .add_event::<DevCommandParsed>()
+            .add_event::<DevCommandExecuted>()
+            .add_event::<DevCommandFailed>()
+            .add_event::<ToolPatched>();
+        // The resources stay so the setup code compiles and runs unchanged without the feature
+        if !cfg!(feature = "dev_tools") {
+            return;
+        }
+        app.configure_sets(PreUpdate, (DevConsoleSet::Replay, DevConsoleSet::Dispatch).chain().after(InputSystem))
+            .add_plugins((ConsoleReplayPlugin, CommandRoutingPlugin, DevJobsPlugin))
+            .add_systems(PreUpdate, (dispatch_console_frontends, run_keybindings).chain().in_set(DevConsoleSet::Dispatch));
+    }
+}
+
+/// Rebuild the completions if the commands, tools or presets changed since they were last built.
+fn refresh_completions(world: &mut World) {
+    // Comparing the ticks rather than checking for changes since the last rebuild also sees
+    // the changes made by the lines run in the same system
+    let built_from = Some((
+        world.resource_ref::<CLIToolBox>().last_changed(),
+        world.get_resource_ref::<ToolPresets>().map(|presets| presets.last_changed()),
+    ));
+    if world.resource::<CompletionCache>().built_from == built_from {
+        return;
+    }
+    let tree = world.resource::<CLIToolBox>().completion_tree(world);
+    let mut cache = world.resource_mut::<CompletionCache>();
+    cache.tree = tree;
+    cache.built_from = built_from;
+    cache.sent.clear();
+}
+
+fn dispatch_console_frontends(world: &mut World) {
+    refresh_completions(world);
+    world.resource_scope(|world, mut frontends: Mut<DevConsoleFrontends>| {
+        world.resource_scope(|_, mut cache: Mut<CompletionCache>| {
+            for (id, frontend) in frontends.frontends.iter_mut() {
+                if cache.sent.insert(*id) {
+                    frontend.update_completions(&cache.tree);
+                }
+            }
+        });
+
+        for (id, frontend) in frontends.frontends.iter_mut() {
+
+            for line in frontend.read_input() {
+                let output = world.resource_scope(|world, toolbox: Mut<CLIToolBox>| {
+                    toolbox.execute_from(&line, frontend.permission(), CommandSource::Frontend(*id), world)
+                });
+
+                for line in output {
+                    if line.level <= Level::WARN {
+                        frontend.report_error(&line.message);
+                    } else {
+                        frontend.write_output(&line.message);
+                    }
+                }
+                frontend.finish_command();
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::test_commands::SetGold;
+
+    /// Counts the completions it receives, and submits `help` every frame.
+    struct CountingFrontend(Arc<Mutex<usize>>);
+
+    impl DevConsoleFrontend for CountingFrontend {
+        fn read_input(&mut self) -> Vec<String> {
+            vec!["help".to_string()]
+        }
+
+        fn write_output(&mut self, _line: &str) {}
+
+        fn report_error(&mut self, _error: &str) {}
+
+        fn update_completions(&mut self, _completions: &CompletionTree) {
+            *self.0.lock().unwrap() += 1;
+        }
+    }
+
+    #[test]
+    fn completions_are_sent_when_they_change() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, DevConsolePlugin))
+            .insert_resource(CLIToolBox::default());
+        let updates = Arc::new(Mutex::new(0));
+        app.world_mut().resource_mut::<DevConsoleFrontends>().add(CountingFrontend(updates.clone()));
+
+        for _ in 0..3 {
+            app.update();
+        }
+        assert_eq!(*updates.lock().unwrap(), 1);
+
+        app.world_mut().resource_mut::<CLIToolBox>().add_command_at::<SetGold>("gold set").unwrap();
+        app.update();
+        app.update();
+        assert_eq!(*updates.lock().unwrap(), 2);
+        assert!(app.world().resource::<CompletionCache>().tree.entries.iter().any(|(path, _)| path == "gold set"));
+
+        let later = Arc::new(Mutex::new(0));
+        app.world_mut().resource_mut::<DevConsoleFrontends>().add(CountingFrontend(later.clone()));
+        app.update();
+        assert_eq!((*updates.lock().unwrap(), *later.lock().unwrap()), (2, 1));
+    }
+}
@@ -0,0 +1,151 @@
+use bevy::log::Level;
+use bevy::prelude::*;
+
+use crate::dev_api::*;
+use crate::frontend::DevConsolePlugin;
+use crate::routing::{CommandRouting, LoopbackTransport};
+use crate::CLIToolBox;
+
+/// A headless app for testing dev commands and tools without a window or a terminal.
+///
+/// The toolbox starts with the built-in parsers only, so tests register the parsers of the other field types they use.
+/// The harness runs lines through the same [`CLIToolBox`] as the consoles, and returns what they
+/// logged so that tests can assert on the output, the errors and the state of the world:
+///
+/// ```ignore
+/// let mut harness = DevConsoleTestHarness::new();
+/// harness.insert_resource(Gold::default());
+/// harness.toolbox_mut().direct_applyer::<u64, _>();
+/// harness.add_command_at::<SetGold>("gold set").unwrap();
+///
+/// let output = harness.run("gold set 50");
+/// assert!(output.is_ok());
+/// assert_eq!(harness.world().resource::<Gold>().0, 50);
+/// ```
+pub struct DevConsoleTestHarness {
+    pub app: App,
+}
+
+impl Default for DevConsoleTestHarness {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DevConsoleTestHarness {
+    /// Create a harness with `MinimalPlugins`, the [`DevConsolePlugin`] and an empty [`CLIToolBox`], and run its first frame.
+    pub fn new() -> Self {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, DevConsolePlugin))
+            .insert_resource(CLIToolBox::default());
+        app.update();
+        DevConsoleTestHarness { app }
+    }
+
+    /// Create a client and a server harness connected by a [`LoopbackTransport`], for testing commands that run on the server.
+    ///
+    /// Commands sent by the client are applied when the server [`advance`](Self::advance)s, and their output
+    /// is logged when the client advances after that.
+    pub fn client_and_server() -> (Self, Self) {
+        let (client_transport, server_transport) = LoopbackTransport::pair();
+        let mut client = Self::new();
+        client.insert_resource(CommandRouting::client(client_transport));
+        let mut server = Self::new();
+        server.insert_resource(CommandRouting::server(server_transport));
+        (client, server)
+    }
+
+    pub fn world(&self) -> &World {
+        self.app.world()
+    }
+
+    pub fn world_mut(&mut self) -> &mut World {
+        self.app.world_mut()
+    }
+
+    pub fn toolbox(&self) -> &CLIToolBox {
+        self.app.world().resource::<CLIToolBox>()
+    }
+
+    pub fn toolbox_mut(&mut self) -> Mut<'_, CLIToolBox> {
+        self.app.world_mut().resource_mut::<CLIToolBox>()
+    }
+
+    pub fn insert_resource<R: Resource>(&mut self, resource: R) -> &mut Self {
+        self.app.insert_resource(resource);
+        self
+    }
+
+    pub fn add_command<T: DevCommand>(&mut self) -> Result<(), DevToolParseError> {
+        self.toolbox_mut().add_command::<T>()
+    }
+
+    pub fn add_command_at<T: DevCommand>(&mut self, path: &str) -> Result<(), DevToolParseError> {
+        self.toolbox_mut().add_command_at::<T>(path)
+    }
+
+    pub fn add_reversible_command_at<T: ReversibleDevCommand>(&mut self, path: &str) -> Result<(), DevToolParseError> {
+        self.toolbox_mut().add_reversible_command_at::<T>(path)
+    }
+
+    pub fn add_tool<T: ModalDevTool>(&mut self) -> Result<(), DevToolParseError> {
+        self.toolbox_mut().add_tool::<T>()
+    }
+
+    /// Run a line as if it was typed in a console, then advance one frame so that systems see its effects.
+    pub fn run(&mut self, line: &str) -> HarnessOutput {
+        self.run_as(line, PermissionLevel::Admin)
+    }
+
+    /// Run a line as if it was typed in a console whose commands run at the given permission level.
+    pub fn run_as(&mut self, line: &str, caller: PermissionLevel) -> HarnessOutput {
+        let output = self.app.world_mut().resource_scope(|world, toolbox: Mut<CLIToolBox>| {
+            toolbox.execute_as(line, caller, world)
+        });
+        self.app.update();
+
+        let mut result = HarnessOutput::default();
+        for line in output {
+            if line.level <= Level::WARN {
+                result.errors.push(line.message);
+            } else {
+                result.output.push(line.message);
+            }
+        }
+        result
+    }
+
+    /// Run the app for the given number of frames.
+    pub fn advance(&mut self, frames: usize) -> &mut Self {
+        for _ in 0..frames {
+            self.app.update();
+        }
+        self
+    }
+}
+
+/// What a line run by [`DevConsoleTestHarness::run`] logged.
+#[derive(Debug, Clone, Default)]
+pub struct HarnessOutput {
+    /// The info and debug lines, in order.
+    pub output: Vec<String>,
+    /// The warnings and errors, in order.
+    pub errors: Vec<String>,
+}
+
+impl HarnessOutput {
+    /// Whether the line ran without reporting any error.
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Whether any output line contains `text`.
+    pub fn contains(&self, text: &str) -> bool {
+        self.output.iter().any(|line| line.contains(text))
+    }
+
+    /// Whether any error contains `text`.
+    pub fn has_error(&self, text: &str) -> bool {
+        self.errors.iter().any(|line| line.contains(text))
+    }
+}
@@ -0,0 +1,333 @@
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+use crate::dev_api::{CommandOutcome, DevToolParseError, PermissionLevel};
+use crate::events::{command_failed, current_source, send_lifecycle_event, CommandSource, ToolPatched};
+use crate::remote::ConnectionId;
+use crate::routing::PeerId;
+use crate::CLIToolBox;
+
+/// The changes made from the console that can be undone, most recent last.
+///
+/// Tool patches keep the previous values of the fields they set, and [`ReversibleDevCommand`](crate::dev_api::ReversibleDevCommand)s
+/// keep the command that undoes them. Undoing an entry moves its own inverse to the redo stack,
+/// and making a new change clears the redo stack.
+///
+/// Each [`HistoryOwner`] has its own stacks, so a client of a server or a remote console only undoes its own changes.
+#[derive(Resource)]
+pub struct DevHistory {
+    stacks: HashMap<HistoryOwner, HistoryStacks>,
+    /// How many entries are kept for each owner, the oldest being dropped first.
+    pub max_len: usize,
+}
+
+impl Default for DevHistory {
+    fn default() -> Self {
+        DevHistory {
+            stacks: HashMap::new(),
+            max_len: 100,
+        }
+    }
+}
+
+/// Whose changes a history holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HistoryOwner {
+    /// The frontends, key bindings, replays and code of this app, which share one history.
+    Local,
+    /// A remote console connection.
+    Remote(ConnectionId),
+    /// A client of this server.
+    Peer(PeerId),
+}
+
+impl From<CommandSource> for HistoryOwner {
+    fn from(source: CommandSource) -> Self {
+        match source {
+            CommandSource::Remote(connection) => HistoryOwner::Remote(connection),
+            CommandSource::Peer(peer) => HistoryOwner::Peer(peer),
+            CommandSource::Frontend(_) | CommandSource::Keybinding | CommandSource::Replay | CommandSource::Code => HistoryOwner::Local,
+        }
+    }
+}
+
+#[derive(Default)]
+struct HistoryStacks {
+    undo: Vec<HistoryEntry>,
+    redo: Vec<HistoryEntry>,
+}
+
+/// A change that can be reverted.
+pub enum HistoryEntry {
+    /// Set fields of the tool with the given qualified name back to these values.
+    ToolPatch { tool: String, values: HashMap<String, Box<dyn Reflect>> },
+    /// Run this command, registered under the given qualified path.
+    Command { path: String, command: Box<dyn Reflect> },
+    /// Undo the command sent to the server under the given qualified path, or redo it if `undo` is false,
+    /// and run the `local` command here for commands that also ran here.
+    Routed { path: String, local: Option<Box<dyn Reflect>>, undo: bool },
+}
+
+impl std::fmt::Display for HistoryEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HistoryEntry::ToolPatch { tool, values } => {
+                let mut fields = values.keys().map(|field| field.as_str()).collect::<Vec<_>>();
+                fields.sort();
+                write!(f, "{} {}", tool, fields.join(", "))
+            },
+            HistoryEntry::Command { path, command } => write!(f, "{} {:?}", path, command),
+            HistoryEntry::Routed { path, .. } => write!(f, "{} on the server", path),
+        }
+    }
+}
+
+impl DevHistory {
+    /// Record a new change of `owner`, clearing its redo stack.
+    pub fn push(&mut self, owner: HistoryOwner, entry: HistoryEntry) {
+        let stacks = self.stacks.entry(owner).or_default();
+        stacks.redo.clear();
+        stacks.undo.push(entry);
+        if stacks.undo.len() > self.max_len {
+            stacks.undo.remove(0);
+        }
+    }
+
+    /// Record a new change of the source of the command being run.
+    pub fn record(world: &mut World, entry: HistoryEntry) {
+        let owner = HistoryOwner::from(current_source(world));
+        world.get_resource_or_insert_with(DevHistory::default).push(owner, entry);
+    }
+
+    fn stacks(&mut self, owner: HistoryOwner) -> &mut HistoryStacks {
+        self.stacks.entry(owner).or_default()
+    }
+}
+
+/// Recording tool patches, and the `undo` and `redo` builtins.
+impl CLIToolBox {
+    /// Patch the fields of a tool, recording their previous values so the patch can be undone.
+    pub fn patch_tool(&self, qualified_name: &str, patch: HashMap<String, Box<dyn Reflect>>, world: &mut World) -> Result<(), DevToolParseError> {
+        let previous = self.insert_tool_values(qualified_name, patch, world)?;
+        DevHistory::record(world, HistoryEntry::ToolPatch {
+            tool: qualified_name.to_string(),
+            values: previous,
+        });
+        Ok(())
+    }
+
+    /// Set the fields of a tool and send a [`ToolPatched`], returning the previous values of the fields.
+    fn insert_tool_values(&self, qualified_name: &str, values: HashMap<String, Box<dyn Reflect>>, world: &mut World) -> Result<HashMap<String, Box<dyn Reflect>>, DevToolParseError> {
+        let patched = values.iter().map(|(field, value)| (field.clone(), value.clone_value())).collect();
+        let previous = (self.tool_insert_fn[qualified_name])(world, values)?;
+        let source = current_source(world);
+        send_lifecycle_event(world, ToolPatched {
+            tool: qualified_name.to_string(),
+            values: patched,
+            source,
+        });
+        Ok(previous)
+    }
+
+    /// Revert the most recent change of the source of the command being run, for a caller at the `caller` level.
+    pub fn undo(&self, caller: PermissionLevel, world: &mut World) {
+        self.step_history(true, caller, world);
+    }
+
+    /// Reapply the most recently undone change of the source of the command being run, for a caller at the `caller` level.
+    pub fn redo(&self, caller: PermissionLevel, world: &mut World) {
+        self.step_history(false, caller, world);
+    }
+
+    /// Revert the top entry of the undo or redo stack, moving its inverse to the other stack.
+    ///
+    /// The caller needs the level of the command or tool that made the change, otherwise the entry stays where it is.
+    fn step_history(&self, undo: bool, caller: PermissionLevel, world: &mut World) {
+        let name = if undo { "undo" } else { "redo" };
+        let owner = HistoryOwner::from(current_source(world));
+        let Some(entry) = world.get_resource_mut::<DevHistory>().and_then(|mut history| {
+            let stacks = history.stacks(owner);
+            if undo { stacks.undo.pop() } else { stacks.redo.pop() }
+        }) else {
+            warn!("Nothing to {}", name);
+            return;
+        };
+        if let Some((changed_by, required)) = self.history_permission(&entry) {
+            if let Err(err) = self.check_permission(&changed_by, required, caller, world) {
+                error!("{}", err);
+                command_failed(world, name, err);
+                let mut history = world.resource_mut::<DevHistory>();
+                let stacks = history.stacks(owner);
+                if undo { stacks.undo.push(entry) } else { stacks.redo.push(entry) }
+                return;
+            }
+        }
+        info!("{}: {}", if undo { "Undo" } else { "Redo" }, entry);
+        if let Some(inverse) = self.revert(entry, world) {
+            let mut history = world.resource_mut::<DevHistory>();
+            let stacks = history.stacks(owner);
+            if undo { stacks.redo.push(inverse) } else { stacks.undo.push(inverse) }
+        }
+    }
+
+    /// The name and level of the command or tool that made the change of a history entry, if it is still registered.
+    fn history_permission(&self, entry: &HistoryEntry) -> Option<(String, PermissionLevel)> {
+        match entry {
+            HistoryEntry::ToolPatch { tool, .. } => self.tool_metadatas.get(tool).map(|metadata| (metadata.name.to_string(), metadata.permission)),
+            HistoryEntry::Command { path, .. } | HistoryEntry::Routed { path, .. } => {
+                self.metadatas.get(path).map(|metadata| (metadata.path.clone(), metadata.permission))
+            },
+        }
+    }
+
+    /// Apply a history entry, returning the entry that reverts it.
+    fn revert(&self, entry: HistoryEntry, world: &mut World) -> Option<HistoryEntry> {
+        match entry {
+            HistoryEntry::ToolPatch { tool, values } => {
+                if !self.tool_insert_fn.contains_key(&tool) {
+                    error!("Tool is no longer registered: {}", tool);
+                    return None;
+                }
+                match self.insert_tool_values(&tool, values, world) {
+                    Ok(previous) => Some(HistoryEntry::ToolPatch { tool, values: previous }),
+                    Err(err) => {
+                        error!("Failed to revert {}: {}", tool, err);
+                        None
+                    },
+                }
+            },
+            HistoryEntry::Command { path, command } => {
+                let inverse = self.revert_command(&path, command, world)?;
+                Some(HistoryEntry::Command { path, command: inverse })
+            },
+            HistoryEntry::Routed { path, local, undo } => {
+                if !self.route_history(undo, world) {
+                    return None;
+                }
+                let local = match local {
+                    Some(command) => Some(self.revert_command(&path, command, world)?),
+                    None => None,
+                };
+                Some(HistoryEntry::Routed { path, local, undo: !undo })
+            },
+        }
+    }
+
+    /// Run the command of a history entry, returning its inverse.
+    fn revert_command(&self, path: &str, command: Box<dyn Reflect>, world: &mut World) -> Option<Box<dyn Reflect>> {
+        let Some(metadata) = self.metadatas.get(path) else {
+            error!("Command is no longer registered: {}", path);
+            return None;
+        };
+        let command = self.before_dev_command(command.as_ref(), metadata, world)?;
+        let inverse = self.apply_dev_command(command.as_ref(), metadata, world);
+        self.after_dev_command(command.as_ref(), metadata, &CommandOutcome::Applied, world);
+        if inverse.is_none() {
+            error!("Command is no longer reversible, dropped it from the history: {}", path);
+        }
+        inverse
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::dev_api::{ArgRequirement, DevCommand, ReversibleDevCommand};
+    use crate::dev_api::ModalDevTool;
+    use crate::harness::DevConsoleTestHarness;
+    use crate::test_commands::Gold;
+    use crate::test_tool::DevFlyCamera;
+
+    /// Sets the player's gold, at the admin level.
+    #[derive(Reflect, Debug, Default)]
+    struct SetGoldAsAdmin {
+        amount: u64,
+    }
+
+    impl bevy::ecs::world::Command for SetGoldAsAdmin {
+        fn apply(self, world: &mut World) {
+            world.resource_mut::<Gold>().0 = self.amount;
+        }
+    }
+
+    impl FromStr for SetGoldAsAdmin {
+        type Err = DevToolParseError;
+        fn from_str(_s: &str) -> Result<Self, DevToolParseError> {
+            Err(DevToolParseError::InvalidToolData)
+        }
+    }
+
+    impl DevCommand for SetGoldAsAdmin {
+        fn short_description() -> Option<&'static str> {
+            None
+        }
+
+        fn permission() -> PermissionLevel {
+            PermissionLevel::Admin
+        }
+
+        fn arguments() -> Vec<(&'static str, ArgRequirement)> {
+            vec![("amount", ArgRequirement::Required)]
+        }
+    }
+
+    impl ReversibleDevCommand for SetGoldAsAdmin {
+        fn inverse(&self, world: &World) -> Self {
+            SetGoldAsAdmin { amount: world.resource::<Gold>().0 }
+        }
+    }
+
+    #[test]
+    fn undo_needs_the_level_of_the_change() {
+        let mut harness = DevConsoleTestHarness::new();
+        harness.insert_resource(Gold(1));
+        harness.toolbox_mut().direct_applyer::<u64, _>();
+        harness.add_reversible_command_at::<SetGoldAsAdmin>("gold admin set").unwrap();
+        harness.run("gold admin set 9");
+
+        let output = harness.run_as("undo", PermissionLevel::Cheat);
+        assert!(output.has_error("permission denied"), "{:?}", output);
+        assert_eq!(harness.world().resource::<Gold>().0, 9);
+
+        // The refused entry is still there for a caller that may undo it
+        assert!(harness.run("undo").is_ok());
+        assert_eq!(harness.world().resource::<Gold>().0, 1);
+    }
+
+    fn patch_speed(harness: &mut DevConsoleTestHarness, field: &str, speed: f32) {
+        let patch = HashMap::from([(field.to_string(), Box::new(Some(speed)) as Box<dyn Reflect>)]);
+        harness.world_mut().resource_scope(|world, toolbox: Mut<CLIToolBox>| {
+            toolbox.patch_tool(&DevFlyCamera::metadata().qualified_name(), patch, world)
+        }).unwrap();
+    }
+
+    fn speeds(harness: &DevConsoleTestHarness) -> (Option<f32>, Option<f32>) {
+        let camera = harness.world().resource::<DevFlyCamera>();
+        (camera.movement_speed, camera.turn_speed)
+    }
+
+    #[test]
+    fn tool_patches_are_undone_and_redone() {
+        let mut harness = DevConsoleTestHarness::new();
+        harness.insert_resource(DevFlyCamera::default());
+        harness.add_tool::<DevFlyCamera>().unwrap();
+        patch_speed(&mut harness, "movement_speed", 5.0);
+        patch_speed(&mut harness, "turn_speed", 1.0);
+        assert_eq!(speeds(&harness), (Some(5.0), Some(1.0)));
+
+        assert!(harness.run("undo").is_ok());
+        assert_eq!(speeds(&harness), (Some(5.0), Some(10.0)));
+        assert!(harness.run("undo").is_ok());
+        assert_eq!(speeds(&harness), (Some(3.0), Some(10.0)));
+        assert!(harness.run("redo").is_ok());
+        assert_eq!(speeds(&harness), (Some(5.0), Some(10.0)));
+
+        // A new patch clears what could be redone
+        patch_speed(&mut harness, "movement_speed", 2.0);
+        assert!(harness.run("redo").has_error("Nothing to redo"));
+        assert!(harness.run("undo").is_ok());
+        assert_eq!(speeds(&harness), (Some(5.0), Some(10.0)));
+    }
+}
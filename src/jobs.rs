@@ -0,0 +1,176 @@
+use bevy::prelude::*;
+
+use crate::dev_api::*;
+use crate::CLIToolBox;
+
+/// Polls the [`DevJob`]s started by long-running dev commands once per frame.
+pub struct DevJobsPlugin;
+
+impl Plugin for DevJobsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DevJobs>()
+            .add_systems(Update, run_dev_jobs);
+    }
+}
+
+/// Resource holding the running [`DevJob`]s.
+///
+/// - `jobs` lists the running jobs with their progress.
+/// - `cancel <id>` cancels a job, and `cancel` alone cancels the most recent one, as Ctrl-C does in the consoles.
+#[derive(Resource, Default)]
+pub struct DevJobs {
+    jobs: Vec<RunningJob>,
+    next_id: u64,
+}
+
+struct RunningJob {
+    id: u64,
+    name: String,
+    job: Box<dyn DevJob>,
+    progress: Option<f32>,
+    /// The number of frames the job ran for.
+    frames: u32,
+    /// The last tenth of the work reported to the console.
+    reported: u32,
+}
+
+impl DevJobs {
+    /// Start a job, polled from the next frame on. Returns the id used to cancel it.
+    pub fn start(&mut self, name: impl Into<String>, job: impl DevJob) -> u64 {
+        self.next_id += 1;
+        let id = self.next_id;
+        let name = name.into();
+        info!("Started job {}: {}", id, name);
+        self.jobs.push(RunningJob {
+            id,
+            name,
+            job: Box::new(job),
+            progress: None,
+            frames: 0,
+            reported: 0,
+        });
+        id
+    }
+}
+
+impl CLIToolBox {
+    /// Handle the `jobs` builtin.
+    pub fn jobs_command(&self, world: &mut World) {
+        let jobs = world.get_resource_or_insert_with(DevJobs::default);
+        if jobs.jobs.is_empty() {
+            info!("No running jobs");
+            return;
+        }
+        for job in jobs.jobs.iter() {
+            match job.progress {
+                Some(progress) => info!("  {} - {} ({:.0}%, {} frames)", job.id, job.name, progress * 100.0, job.frames),
+                None => info!("  {} - {} ({} frames)", job.id, job.name, job.frames),
+            }
+        }
+    }
+
+    /// Handle the `cancel` builtin.
+    pub fn cancel_command(&self, args: &[&str], world: &mut World) {
+        let mut jobs = world.get_resource_or_insert_with(DevJobs::default);
+        let index = match args {
+            [] => jobs.jobs.len().checked_sub(1),
+            [id] => match id.parse::<u64>() {
+                Ok(id) => jobs.jobs.iter().position(|job| job.id == id),
+                Err(_) => {
+                    error!("Usage: cancel [id]");
+                    return;
+                },
+            },
+            _ => {
+                error!("Usage: cancel [id]");
+                return;
+            },
+        };
+        let Some(index) = index else {
+            match args.first() {
+                Some(id) => error!("No running job {}", id),
+                None => info!("No running jobs"),
+            }
+            return;
+        };
+
+        let mut job = jobs.jobs.remove(index);
+        job.job.cancel(world);
+        info!("Cancelled job {}: {}", job.id, job.name);
+    }
+}
+
+fn run_dev_jobs(world: &mut World) {
+    let mut jobs = std::mem::take(&mut world.resource_mut::<DevJobs>().jobs);
+    jobs.retain_mut(|job| {
+        job.frames += 1;
+        match job.job.poll(world) {
+            JobStatus::Running(progress) => {
+                job.progress = progress.map(|progress| progress.clamp(0.0, 1.0));
+                let tenths = job.progress.map_or(0, |progress| (progress * 10.0) as u32);
+                if tenths > job.reported {
+                    job.reported = tenths;
+                    info!("Job {} ({}): {}%", job.id, job.name, tenths * 10);
+                }
+                true
+            },
+            JobStatus::Done => {
+                info!("Finished job {} ({}) after {} frames", job.id, job.name, job.frames);
+                false
+            },
+        }
+    });
+
+    // Jobs started while polling were added to the resource
+    let mut resource = world.resource_mut::<DevJobs>();
+    jobs.append(&mut resource.jobs);
+    resource.jobs = jobs;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::harness::DevConsoleTestHarness;
+    use crate::test_commands::BenchmarkSpawn;
+
+    fn harness() -> DevConsoleTestHarness {
+        let mut harness = DevConsoleTestHarness::new();
+        harness.toolbox_mut().direct_applyer::<u64, _>();
+        harness.add_command_at::<BenchmarkSpawn>("benchmark spawn").unwrap();
+        harness
+    }
+
+    fn entities(harness: &DevConsoleTestHarness) -> usize {
+        harness.world().iter_entities().count()
+    }
+
+    #[test]
+    fn jobs_report_progress_until_done() {
+        let mut harness = harness();
+        let before = entities(&harness);
+        let output = harness.run("benchmark spawn 10 5");
+        assert!(output.contains("Started job 1: spawn 10 entities over 5 frames"), "{:?}", output);
+
+        let output = harness.run("jobs");
+        assert!(output.contains("1 - spawn 10 entities over 5 frames (20%, 1 frames)"), "{:?}", output);
+        assert_eq!(entities(&harness), before + 4);
+
+        harness.advance(3);
+        assert!(harness.run("jobs").contains("No running jobs"));
+        assert_eq!(entities(&harness), before + 10);
+    }
+
+    #[test]
+    fn jobs_can_be_cancelled_without_cheats() {
+        let mut harness = harness();
+        let before = entities(&harness);
+        harness.run("benchmark spawn 10 5");
+        harness.run("sv_cheats 0");
+
+        // Ctrl-C sends `cancel` from consoles that cannot run cheats
+        let output = harness.run_as("cancel", PermissionLevel::ReadOnly);
+        assert!(output.contains("Cancelled job 1: spawn 10 entities over 5 frames"), "{:?}", output);
+        assert_eq!(entities(&harness), before);
+        assert!(harness.run("jobs").contains("No running jobs"));
+    }
+}
@@ -0,0 +1,376 @@
+use bevy::input::gamepad::{GamepadButton, GamepadButtonType, Gamepads};
+use bevy::prelude::*;
+
+use crate::confirm::strip_yes;
+use crate::dev_api::*;
+use crate::events::CommandSource;
+use crate::log_capture;
+use crate::overlay::OverlayConsole;
+use crate::CLIToolBox;
+
+/// Runs command lines when key chords are pressed, so that debug tools can be flipped without typing.
+///
+/// A chord is a `+` separated list of keys, such as `f1`, `ctrl+shift+g` or `pad:start`, where `pad:`
+/// names a button of any gamepad. A chord triggers on the frame its last key is pressed while the others
+/// are held, and only when no other modifier is held.
+///
+/// - `bind <chord> <command line>` binds a chord, replacing its previous binding.
+/// - `unbind <chord>` removes a binding.
+/// - `binds` lists the bindings.
+///
+/// Tools are toggled by binding `toggle <tool>`, as done by [`Keybindings::bind_tool_toggle`].
+/// Bindings are not triggered while the overlay console is open, as its keys are being typed.
+#[derive(Resource, Default)]
+pub struct Keybindings {
+    binds: Vec<(KeyChord, String)>,
+}
+
+impl Keybindings {
+    /// Bind a chord to a command line, replacing its previous binding.
+    pub fn bind(&mut self, chord: &str, line: &str) -> Result<(), DevToolParseError> {
+        let chord = chord.parse::<KeyChord>()?;
+        self.binds.retain(|(bound, _)| *bound != chord);
+        self.binds.push((chord, line.trim().to_string()));
+        Ok(())
+    }
+
+    /// Bind a chord to toggling the tool `T`.
+    ///
+    /// Without the `dev_tools` feature this does nothing, like registering the tool.
+    pub fn bind_tool_toggle<T: ModalDevTool>(&mut self, chord: &str) -> Result<(), DevToolParseError> {
+        if !cfg!(feature = "dev_tools") {
+            return Ok(());
+        }
+        self.bind(chord, &format!("toggle {}", T::metadata().qualified_name()))
+    }
+
+    /// Remove the binding of a chord, returning its command line.
+    pub fn unbind(&mut self, chord: &str) -> Result<Option<String>, DevToolParseError> {
+        let chord = chord.parse::<KeyChord>()?;
+        let Some(idx) = self.binds.iter().position(|(bound, _)| *bound == chord) else {
+            return Ok(None);
+        };
+        Ok(Some(self.binds.remove(idx).1))
+    }
+
+    /// Every binding as its chord and command line, in the order they were bound.
+    pub fn iter(&self) -> impl Iterator<Item = (String, &str)> + '_ {
+        self.binds.iter().map(|(chord, line)| (chord.to_string(), line.as_str()))
+    }
+
+    /// The command lines of the chords triggered this frame.
+    fn triggered(&self, keys: Option<&ButtonInput<KeyCode>>, pads: Option<(&ButtonInput<GamepadButton>, &Gamepads)>) -> Vec<String> {
+        self.binds
+            .iter()
+            .filter(|(chord, _)| chord.is_triggered(keys, pads))
+            .map(|(_, line)| line.clone())
+            .collect()
+    }
+}
+
+/// A set of keys and gamepad buttons pressed together.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyChord(Vec<ChordKey>);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ChordKey {
+    Key(KeyCode),
+    /// A modifier, matched by either of its keys.
+    Modifier(Modifier),
+    Pad(GamepadButtonType),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Modifier {
+    Ctrl,
+    Shift,
+    Alt,
+    Super,
+}
+
+const MODIFIERS: [Modifier; 4] = [Modifier::Ctrl, Modifier::Shift, Modifier::Alt, Modifier::Super];
+
+impl Modifier {
+    fn keys(self) -> [KeyCode; 2] {
+        match self {
+            Modifier::Ctrl => [KeyCode::ControlLeft, KeyCode::ControlRight],
+            Modifier::Shift => [KeyCode::ShiftLeft, KeyCode::ShiftRight],
+            Modifier::Alt => [KeyCode::AltLeft, KeyCode::AltRight],
+            Modifier::Super => [KeyCode::SuperLeft, KeyCode::SuperRight],
+        }
+    }
+}
+
+impl ChordKey {
+    fn pressed(&self, keys: Option<&ButtonInput<KeyCode>>, pads: Option<(&ButtonInput<GamepadButton>, &Gamepads)>) -> bool {
+        match self {
+            ChordKey::Key(key) => keys.is_some_and(|keys| keys.pressed(*key)),
+            ChordKey::Modifier(modifier) => keys.is_some_and(|keys| keys.any_pressed(modifier.keys())),
+            ChordKey::Pad(button) => pads.is_some_and(|(buttons, gamepads)| {
+                gamepads.iter().any(|gamepad| buttons.pressed(GamepadButton::new(gamepad, *button)))
+            }),
+        }
+    }
+
+    fn just_pressed(&self, keys: Option<&ButtonInput<KeyCode>>, pads: Option<(&ButtonInput<GamepadButton>, &Gamepads)>) -> bool {
+        match self {
+            ChordKey::Key(key) => keys.is_some_and(|keys| keys.just_pressed(*key)),
+            ChordKey::Modifier(modifier) => keys.is_some_and(|keys| keys.any_just_pressed(modifier.keys())),
+            ChordKey::Pad(button) => pads.is_some_and(|(buttons, gamepads)| {
+                gamepads.iter().any(|gamepad| buttons.just_pressed(GamepadButton::new(gamepad, *button)))
+            }),
+        }
+    }
+}
+
+impl KeyChord {
+    fn is_triggered(&self, keys: Option<&ButtonInput<KeyCode>>, pads: Option<(&ButtonInput<GamepadButton>, &Gamepads)>) -> bool {
+        let extra_modifier = MODIFIERS
+            .iter()
+            .filter(|modifier| !self.0.contains(&ChordKey::Modifier(**modifier)))
+            .any(|modifier| keys.is_some_and(|keys| keys.any_pressed(modifier.keys())));
+
+        !extra_modifier
+            && self.0.iter().all(|key| key.pressed(keys, pads))
+            && self.0.iter().any(|key| key.just_pressed(keys, pads))
+    }
+}
+
+impl std::str::FromStr for KeyChord {
+    type Err = DevToolParseError;
+
+    fn from_str(s: &str) -> Result<Self, DevToolParseError> {
+        let keys = s
+            .split('+')
+            .map(|part| {
+                let part = part.trim().to_lowercase();
+                let key = match part.strip_prefix("pad:") {
+                    Some(button) => parse_pad_button(button).map(ChordKey::Pad),
+                    None => parse_modifier(&part).map(ChordKey::Modifier).or_else(|| parse_key(&part).map(ChordKey::Key)),
+                };
+                key.ok_or_else(|| {
+                    error!("Unknown key in chord {}: {}", s, part);
+                    DevToolParseError::InvalidToolData
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(KeyChord(keys))
+    }
+}
+
+impl std::fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let parts = self.0
+            .iter()
+            .map(|key| match key {
+                ChordKey::Key(key) => key_name(*key),
+                ChordKey::Modifier(modifier) => format!("{:?}", modifier).to_lowercase(),
+                ChordKey::Pad(button) => format!("pad:{:?}", button).to_lowercase(),
+            })
+            .collect::<Vec<_>>();
+        write!(f, "{}", parts.join("+"))
+    }
+}
+
+fn parse_modifier(name: &str) -> Option<Modifier> {
+    match name {
+        "ctrl" | "control" => Some(Modifier::Ctrl),
+        "shift" => Some(Modifier::Shift),
+        "alt" | "option" => Some(Modifier::Alt),
+        "super" | "cmd" | "meta" | "win" => Some(Modifier::Super),
+        _ => None,
+    }
+}
+
+/// The keys that can be named in chords, by their lowercase name.
+const KEY_NAMES: &[(&str, KeyCode)] = &[
+    ("a", KeyCode::KeyA), ("b", KeyCode::KeyB), ("c", KeyCode::KeyC), ("d", KeyCode::KeyD), ("e", KeyCode::KeyE),
+    ("f", KeyCode::KeyF), ("g", KeyCode::KeyG), ("h", KeyCode::KeyH), ("i", KeyCode::KeyI), ("j", KeyCode::KeyJ),
+    ("k", KeyCode::KeyK), ("l", KeyCode::KeyL), ("m", KeyCode::KeyM), ("n", KeyCode::KeyN), ("o", KeyCode::KeyO),
+    ("p", KeyCode::KeyP), ("q", KeyCode::KeyQ), ("r", KeyCode::KeyR), ("s", KeyCode::KeyS), ("t", KeyCode::KeyT),
+    ("u", KeyCode::KeyU), ("v", KeyCode::KeyV), ("w", KeyCode::KeyW), ("x", KeyCode::KeyX), ("y", KeyCode::KeyY),
+    ("z", KeyCode::KeyZ),
+    ("0", KeyCode::Digit0), ("1", KeyCode::Digit1), ("2", KeyCode::Digit2), ("3", KeyCode::Digit3), ("4", KeyCode::Digit4),
+    ("5", KeyCode::Digit5), ("6", KeyCode::Digit6), ("7", KeyCode::Digit7), ("8", KeyCode::Digit8), ("9", KeyCode::Digit9),
+    ("f1", KeyCode::F1), ("f2", KeyCode::F2), ("f3", KeyCode::F3), ("f4", KeyCode::F4), ("f5", KeyCode::F5),
+    ("f6", KeyCode::F6), ("f7", KeyCode::F7), ("f8", KeyCode::F8), ("f9", KeyCode::F9), ("f10", KeyCode::F10),
+    ("f11", KeyCode::F11), ("f12", KeyCode::F12),
+    ("space", KeyCode::Space), ("enter", KeyCode::Enter), ("tab", KeyCode::Tab), ("escape", KeyCode::Escape),
+    ("backspace", KeyCode::Backspace), ("delete", KeyCode::Delete), ("insert", KeyCode::Insert),
+    ("home", KeyCode::Home), ("end", KeyCode::End), ("pageup", KeyCode::PageUp), ("pagedown", KeyCode::PageDown),
+    ("up", KeyCode::ArrowUp), ("down", KeyCode::ArrowDown), ("left", KeyCode::ArrowLeft), ("right", KeyCode::ArrowRight),
+    ("backquote", KeyCode::Backquote), ("minus", KeyCode::Minus), ("equal", KeyCode::Equal),
+    ("comma", KeyCode::Comma), ("period", KeyCode::Period), ("slash", KeyCode::Slash),
+];
+
+fn parse_key(name: &str) -> Option<KeyCode> {
+    let name = if name == "esc" { "escape" } else { name };
+    KEY_NAMES.iter().find(|(key_name, _)| *key_name == name).map(|(_, key)| *key)
+}
+
+fn key_name(key: KeyCode) -> String {
+    match KEY_NAMES.iter().find(|(_, named)| *named == key) {
+        Some((name, _)) => name.to_string(),
+        None => format!("{:?}", key).to_lowercase(),
+    }
+}
+
+fn parse_pad_button(name: &str) -> Option<GamepadButtonType> {
+    let button = match name {
+        "south" | "a" => GamepadButtonType::South,
+        "east" | "b" => GamepadButtonType::East,
+        "north" | "y" => GamepadButtonType::North,
+        "west" | "x" => GamepadButtonType::West,
+        "lefttrigger" | "lb" => GamepadButtonType::LeftTrigger,
+        "lefttrigger2" | "lt" => GamepadButtonType::LeftTrigger2,
+        "righttrigger" | "rb" => GamepadButtonType::RightTrigger,
+        "righttrigger2" | "rt" => GamepadButtonType::RightTrigger2,
+        "select" | "back" => GamepadButtonType::Select,
+        "start" => GamepadButtonType::Start,
+        "mode" => GamepadButtonType::Mode,
+        "leftthumb" => GamepadButtonType::LeftThumb,
+        "rightthumb" => GamepadButtonType::RightThumb,
+        "dpadup" => GamepadButtonType::DPadUp,
+        "dpaddown" => GamepadButtonType::DPadDown,
+        "dpadleft" => GamepadButtonType::DPadLeft,
+        "dpadright" => GamepadButtonType::DPadRight,
+        _ => return None,
+    };
+    Some(button)
+}
+
+impl CLIToolBox {
+    /// Handle the `bind`, `unbind` and `binds` builtins.
+    pub fn bind_command(&self, name: &str, args: &[&str], world: &mut World) {
+        if !world.contains_resource::<Keybindings>() {
+            world.init_resource::<Keybindings>();
+        }
+        let mut keybindings = world.resource_mut::<Keybindings>();
+        match (name, args) {
+            ("bind", [chord, line @ ..]) if !line.is_empty() => {
+                let line = line.join(" ");
+                // A key binding cannot answer a confirmation
+                if self.is_destructive(&line) && !strip_yes(&line).1 {
+                    error!("'{}' is destructive, bind it with --yes to run it without asking", line);
+                    return;
+                }
+                if keybindings.bind(chord, &line).is_ok() {
+                    info!("Bound {} to {}", chord, line);
+                }
+            },
+            ("unbind", [chord]) => match keybindings.unbind(chord) {
+                Ok(Some(line)) => info!("Unbound {} from {}", chord, line),
+                Ok(None) => warn!("{} is not bound", chord),
+                Err(_) => {},
+            },
+            ("binds", []) => {
+                if keybindings.binds.is_empty() {
+                    info!("No bindings");
+                }
+                for (chord, line) in keybindings.iter() {
+                    info!("  {} - {}", chord, line);
+                }
+            },
+            _ => error!("Usage: bind <chord> <command line> | unbind <chord> | binds"),
+        }
+    }
+}
+
+/// Run the command lines of the chords pressed this frame, logging their output.
+pub fn run_keybindings(world: &mut World) {
+    if world.get_resource::<OverlayConsole>().is_some_and(|console| console.open) {
+        return;
+    }
+    let Some(keybindings) = world.get_resource::<Keybindings>() else {
+        return;
+    };
+    let keys = world.get_resource::<ButtonInput<KeyCode>>();
+    let pads = world.get_resource::<ButtonInput<GamepadButton>>().zip(world.get_resource::<Gamepads>());
+    let triggered = keybindings.triggered(keys, pads);
+
+    for line in triggered {
+        info!("bind> {}", line);
+        let output = world.resource_scope(|world, toolbox: Mut<CLIToolBox>| {
+            toolbox.execute_from(&line, PermissionLevel::Admin, CommandSource::Keybinding, world)
+        });
+        log_capture::log_lines(output);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::harness::DevConsoleTestHarness;
+    use crate::test_commands::{Gold, SetGold};
+
+    #[test]
+    fn chords_are_parsed() {
+        let chord = "Ctrl+shift + g".parse::<KeyChord>().unwrap();
+        assert_eq!(chord, KeyChord(vec![ChordKey::Modifier(Modifier::Ctrl), ChordKey::Modifier(Modifier::Shift), ChordKey::Key(KeyCode::KeyG)]));
+        assert_eq!(chord.to_string(), "ctrl+shift+g");
+        assert_eq!("cmd+esc".parse::<KeyChord>().unwrap().to_string(), "super+escape");
+        assert_eq!("pad:a".parse::<KeyChord>().unwrap(), KeyChord(vec![ChordKey::Pad(GamepadButtonType::South)]));
+
+        assert!("ctrl+hyper".parse::<KeyChord>().is_err());
+        assert!("pad:f1".parse::<KeyChord>().is_err());
+        assert!("ctrl+".parse::<KeyChord>().is_err());
+    }
+
+    #[test]
+    fn chords_trigger_when_their_last_key_is_pressed() {
+        let mut bindings = Keybindings::default();
+        bindings.bind("ctrl+g", "gold print").unwrap();
+        let mut keys = ButtonInput::<KeyCode>::default();
+
+        keys.press(KeyCode::ControlLeft);
+        assert!(bindings.triggered(Some(&keys), None).is_empty());
+        keys.clear();
+        keys.press(KeyCode::KeyG);
+        assert_eq!(bindings.triggered(Some(&keys), None), vec!["gold print".to_string()]);
+
+        // Held keys do not trigger again, and other modifiers prevent the chord
+        keys.clear();
+        assert!(bindings.triggered(Some(&keys), None).is_empty());
+        keys.release(KeyCode::KeyG);
+        keys.press(KeyCode::ShiftLeft);
+        keys.clear();
+        keys.press(KeyCode::KeyG);
+        assert!(bindings.triggered(Some(&keys), None).is_empty());
+    }
+
+    #[test]
+    fn bindings_are_replaced_and_removed() {
+        let mut bindings = Keybindings::default();
+        bindings.bind("f5", "help").unwrap();
+        bindings.bind("F5", "binds").unwrap();
+        assert_eq!(bindings.iter().collect::<Vec<_>>(), vec![("f5".to_string(), "binds")]);
+
+        assert_eq!(bindings.unbind("f5").unwrap(), Some("binds".to_string()));
+        assert_eq!(bindings.unbind("f5").unwrap(), None);
+        assert!(bindings.unbind("hyper").is_err());
+    }
+
+    #[test]
+    fn destructive_lines_are_recognized() {
+        let toolbox = CLIToolBox::default();
+        assert!(toolbox.is_destructive("snapshot load quick"));
+        assert!(toolbox.is_destructive("Snapshot load quick --yes"));
+        assert!(!toolbox.is_destructive("snapshot save quick"));
+        assert!(!toolbox.is_destructive("help"));
+    }
+
+    #[test]
+    fn pressing_a_chord_runs_its_command() {
+        let mut harness = DevConsoleTestHarness::new();
+        harness.insert_resource(Gold(1));
+        harness.toolbox_mut().direct_applyer::<u64, _>();
+        harness.add_command_at::<SetGold>("gold set").unwrap();
+        assert!(harness.run("bind f5 gold set 7").is_ok());
+
+        let mut keys = ButtonInput::<KeyCode>::default();
+        keys.press(KeyCode::F5);
+        harness.insert_resource(keys);
+        harness.advance(1);
+        assert_eq!(harness.world().resource::<Gold>().0, 7);
+    }
+}
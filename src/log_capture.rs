@@ -0,0 +1,413 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fmt::{Debug, Write};
+use std::sync::{Arc, Mutex};
+
+use bevy::log::tracing_subscriber::filter::{Directive, EnvFilter, LevelFilter};
+use bevy::log::tracing_subscriber::{fmt, layer::Context, reload, Layer, Registry};
+use bevy::log::tracing_subscriber::layer::SubscriberExt;
+use bevy::log::Level;
+use bevy::prelude::*;
+use bevy::utils::tracing::field::{Field, Visit};
+use bevy::utils::tracing::{Event, Subscriber};
+use regex::Regex;
+use tracing_log::LogTracer;
+
+use crate::events::CommandSource;
+use crate::CLIToolBox;
+
+/// Replaces bevy's `LogPlugin` with a logger the console can filter and print through.
+///
+/// Every record passing the filter is formatted like `LogPlugin` does and copied into the [`CapturedLogs`] resource.
+/// Formatted records go to stderr until a frontend takes them with [`LogOutput::redirect`], as the terminal console
+/// does to print them above its prompt. The records of a command run by [`capture_output`] are not formatted,
+/// as the console that issued the command shows them. The filter starts from `RUST_LOG`, or `level` and `filter` without it,
+/// and `log level <target> <level>` changes it at runtime.
+///
+/// Disable `LogPlugin` when adding this plugin, and add it first so that the other plugins can log while they build.
+pub struct ConsoleLogPlugin {
+    /// Filters logs using the `EnvFilter` format, as `LogPlugin::filter`.
+    pub filter: String,
+    /// The default level of the targets `filter` does not name.
+    pub level: Level,
+}
+
+impl Default for ConsoleLogPlugin {
+    fn default() -> Self {
+        ConsoleLogPlugin {
+            filter: "wgpu=error,naga=warn".to_string(),
+            level: Level::INFO,
+        }
+    }
+}
+
+impl Plugin for ConsoleLogPlugin {
+    fn build(&self, app: &mut App) {
+        let base = std::env::var(EnvFilter::DEFAULT_ENV)
+            .ok()
+            .filter(|filter| EnvFilter::try_new(filter).is_ok())
+            .unwrap_or_else(|| format!("{},{}", self.level, self.filter));
+        let (filter_layer, handle) = reload::Layer::new(EnvFilter::new(&base));
+
+        let logs = CapturedLogs::default();
+        let output = LogOutput::default();
+        let writer = output.clone();
+        let subscriber = Registry::default()
+            .with(filter_layer)
+            .with(CaptureLayer(Some(logs.clone())))
+            .with(fmt::Layer::default().with_writer(move || writer.clone()));
+        app.insert_resource(logs)
+            .insert_resource(output)
+            .insert_resource(LogFilters { base, targets: Vec::new(), handle });
+
+        let logger_already_set = LogTracer::init().is_err();
+        let subscriber_already_set = bevy::utils::tracing::subscriber::set_global_default(subscriber).is_err();
+        if logger_already_set || subscriber_already_set {
+            error!("Could not set the global logger as it is already set. Consider disabling LogPlugin.");
+        }
+    }
+}
+
+/// Resource holding the filter of the [`ConsoleLogPlugin`].
+#[derive(Resource)]
+pub struct LogFilters {
+    /// The filter the app started with.
+    base: String,
+    /// The directives added by `log level`, by target, applied over `base`.
+    targets: Vec<(String, Directive)>,
+    handle: reload::Handle<EnvFilter, Registry>,
+}
+
+impl LogFilters {
+    /// The current filter, in the `EnvFilter` format.
+    pub fn directives(&self) -> String {
+        self.targets
+            .iter()
+            .fold(self.base.clone(), |directives, (_, directive)| format!("{},{}", directives, directive))
+    }
+
+    /// Log the records of `target` and its modules at `level` and more severe levels.
+    pub fn set_level(&mut self, target: &str, level: &str) -> Result<(), String> {
+        let directive = format!("{}={}", target, level).parse::<Directive>().map_err(|err| err.to_string())?;
+        self.targets.retain(|(existing, _)| existing != target);
+        self.targets.push((target.to_string(), directive));
+
+        // A directive for a target replaces the one the base filter has for it
+        let filter = self.targets
+            .iter()
+            .fold(EnvFilter::new(&self.base), |filter, (_, directive)| filter.add_directive(directive.clone()));
+        self.handle.reload(filter).map_err(|err| err.to_string())
+    }
+}
+
+/// Prints a formatted record, in place of stderr.
+pub type LogPrinter = Box<dyn FnMut(String) + Send>;
+
+/// Resource choosing where the [`ConsoleLogPlugin`] writes formatted records.
+#[derive(Resource, Clone, Default)]
+pub struct LogOutput(Arc<Mutex<Option<LogPrinter>>>);
+
+impl LogOutput {
+    /// Send formatted records to `print` instead of stderr, such as to the printer of a line editor so that it redraws its prompt.
+    pub fn redirect(&self, print: impl FnMut(String) + Send + 'static) {
+        *self.0.lock().unwrap() = Some(Box::new(print));
+    }
+}
+
+impl std::io::Write for LogOutput {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if CAPTURE.with(|capture| !capture.borrow().sinks.is_empty()) {
+            return Ok(buf.len());
+        }
+        match self.0.lock().unwrap().as_mut() {
+            Some(print) => {
+                print(String::from_utf8_lossy(buf).into_owned());
+                Ok(buf.len())
+            },
+            None => std::io::stderr().write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        std::io::stderr().flush()
+    }
+}
+
+/// A log record captured from `tracing`.
+#[derive(Debug, Clone)]
+pub struct LogLine {
+    /// Position of this line in the stream of every captured line, used as a cursor by readers.
+    pub index: u64,
+    pub level: Level,
+    pub target: String,
+    pub message: String,
+    /// Where the command that logged this line came from, for lines logged while a command ran.
+    pub source: Option<CommandSource>,
+}
+
+impl std::fmt::Display for LogLine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:>5} {}: {}", self.level, self.target, self.message)
+    }
+}
+
+/// The most recent log lines, oldest first.
+pub struct LogBuffer {
+    lines: VecDeque<LogLine>,
+    next_index: u64,
+    capacity: usize,
+}
+
+impl LogBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            lines: VecDeque::with_capacity(capacity),
+            next_index: 0,
+            capacity,
+        }
+    }
+
+    fn push(&mut self, level: Level, target: String, message: String, source: Option<CommandSource>) {
+        if self.lines.len() == self.capacity {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(LogLine { index: self.next_index, level, target, message, source });
+        self.next_index += 1;
+    }
+
+    /// Every buffered line with an index of at least `cursor`.
+    pub fn since(&self, cursor: u64) -> Vec<LogLine> {
+        self.lines.iter().filter(|line| line.index >= cursor).cloned().collect()
+    }
+}
+
+/// Resource giving access to the log lines captured by the [`ConsoleLogPlugin`].
+///
+/// Several consoles can read the same lines: each keeps its own cursor and asks for the lines [`since`](LogBuffer::since) it.
+#[derive(Resource, Clone)]
+pub struct CapturedLogs(pub Arc<Mutex<LogBuffer>>);
+
+impl Default for CapturedLogs {
+    fn default() -> Self {
+        CapturedLogs(Arc::new(Mutex::new(LogBuffer::new(1000))))
+    }
+}
+
+impl CapturedLogs {
+    pub fn since(&self, cursor: u64) -> Vec<LogLine> {
+        self.0.lock().unwrap().since(cursor)
+    }
+}
+
+/// The output captured on a thread.
+#[derive(Default)]
+struct CaptureState {
+    /// The lines of each [`capture_output`] running on this thread, innermost last, with the source of its command.
+    sinks: Vec<(CommandSource, Vec<LogLine>)>,
+    /// Whether [`log_lines`] is logging lines that are already in the [`CapturedLogs`].
+    relogging: bool,
+}
+
+thread_local! {
+    static CAPTURE: RefCell<CaptureState> = RefCell::new(CaptureState::default());
+}
+
+/// Run `f`, a command from `source`, collecting the log records that it emits on this thread.
+///
+/// The records still go through the filter of the [`ConsoleLogPlugin`] and into the [`CapturedLogs`], tagged with `source`,
+/// but are not printed, so that the output of a command can be routed to the console that issued it. When the current
+/// subscriber has no [`CaptureLayer`], such as without the plugin, the records of level `INFO` and above are collected
+/// by a subscriber set while `f` runs.
+pub fn capture_output<R>(source: CommandSource, f: impl FnOnce() -> R) -> (R, Vec<LogLine>) {
+    CAPTURE.with(|capture| capture.borrow_mut().sinks.push((source, Vec::new())));
+    let capturing = bevy::utils::tracing::dispatcher::get_default(|dispatch| dispatch.downcast_ref::<CaptureLayer>().is_some());
+    let result = if capturing {
+        f()
+    } else {
+        let subscriber = Registry::default().with(CaptureLayer(None).with_filter(LevelFilter::INFO));
+        bevy::utils::tracing::subscriber::with_default(subscriber, f)
+    };
+    let lines = CAPTURE.with(|capture| capture.borrow_mut().sinks.pop().map(|(_, lines)| lines).unwrap_or_default());
+    (result, lines)
+}
+
+/// Print captured lines through the global logger, for output of commands that no console issued.
+///
+/// The lines are already in the [`CapturedLogs`], so they are not added again.
+pub fn log_lines(lines: impl IntoIterator<Item = LogLine>) {
+    CAPTURE.with(|capture| capture.borrow_mut().relogging = true);
+    for line in lines {
+        match line.level {
+            Level::ERROR => error!("{}", line.message),
+            Level::WARN => warn!("{}", line.message),
+            _ => info!("{}", line.message),
+        }
+    }
+    CAPTURE.with(|capture| capture.borrow_mut().relogging = false);
+}
+
+impl CLIToolBox {
+    /// Handle the `log` builtin.
+    ///
+    /// - `log [--level <level>] [--target <target>] [--count <count>] [regex]` shows the last captured lines,
+    ///   20 by default, keeping the lines at `level` or more severe, from `target` or its modules, and whose message matches `regex`.
+    /// - `log level` shows the filter of the logger, and `log level <target> <level>` changes it for `target` and its modules.
+    pub fn log_command(&self, args: &[&str], world: &mut World) {
+        if args.first() == Some(&"level") {
+            self.log_level_command(&args[1..], world);
+            return;
+        }
+        let mut level = Level::TRACE;
+        let mut target = "";
+        let mut count = 20;
+        let mut pattern = Vec::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let value = match *arg {
+                "--level" | "--target" | "--count" => args.next(),
+                _ => {
+                    pattern.push(*arg);
+                    continue;
+                },
+            };
+            let parsed = match (*arg, value) {
+                ("--level", Some(value)) => value.parse().map(|value| level = value).is_ok(),
+                ("--count", Some(value)) => value.parse().map(|value| count = value).is_ok(),
+                ("--target", Some(value)) => {
+                    target = value;
+                    true
+                },
+                _ => false,
+            };
+            if !parsed {
+                error!("Usage: log [--level <level>] [--target <target>] [--count <count>] [regex]");
+                return;
+            }
+        }
+        let pattern = match Regex::new(&pattern.join(" ")) {
+            Ok(pattern) => pattern,
+            Err(err) => {
+                error!("Invalid regex: {}", err);
+                return;
+            },
+        };
+        let Some(logs) = world.get_resource::<CapturedLogs>() else {
+            error!("Logs are not captured, add the ConsoleLogPlugin");
+            return;
+        };
+
+        // Levels compare by verbosity, so the more severe levels are the smaller ones
+        let lines = logs.since(0)
+            .into_iter()
+            .filter(|line| line.level <= level)
+            .filter(|line| target.is_empty() || line.target == target || line.target.starts_with(&format!("{}::", target)))
+            .filter(|line| pattern.is_match(&line.message))
+            .collect::<Vec<_>>();
+        if lines.is_empty() {
+            info!("No matching log lines");
+        }
+        for line in lines.iter().skip(lines.len().saturating_sub(count)) {
+            info!("{}", line);
+        }
+    }
+
+    fn log_level_command(&self, args: &[&str], world: &mut World) {
+        let Some(mut filters) = world.get_resource_mut::<LogFilters>() else {
+            error!("The log filter cannot change, add the ConsoleLogPlugin");
+            return;
+        };
+        match args {
+            [] => info!("Log filter: {}", filters.directives()),
+            [target, level] => match filters.set_level(target, level) {
+                Ok(()) => info!("Log filter: {}", filters.directives()),
+                Err(err) => error!("Invalid log level {} for {}: {}", level, target, err),
+            },
+            _ => error!("Usage: log level [<target> <level>]"),
+        }
+    }
+}
+
+/// Copies records into the [`CapturedLogs`], if any, and into the innermost [`capture_output`] running on their thread.
+struct CaptureLayer(Option<CapturedLogs>);
+
+impl<S: Subscriber> Layer<S> for CaptureLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+
+        let metadata = event.metadata();
+        let (level, target) = (*metadata.level(), metadata.target().to_string());
+        let (source, relogging) = CAPTURE.with(|capture| {
+            let mut capture = capture.borrow_mut();
+            let relogging = capture.relogging;
+            let Some((source, lines)) = capture.sinks.last_mut() else {
+                return (None, relogging);
+            };
+            lines.push(LogLine {
+                index: lines.len() as u64,
+                level,
+                target: target.clone(),
+                message: visitor.0.clone(),
+                source: Some(*source),
+            });
+            (Some(*source), relogging)
+        });
+        if let (Some(logs), false) = (&self.0, relogging) {
+            logs.0.lock().unwrap().push(level, target, visitor.0, source);
+        }
+    }
+}
+
+/// Formats the `message` field of an event followed by its other fields as `name=value`.
+#[derive(Default)]
+struct MessageVisitor(String);
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.0, "{:?}", value);
+        } else {
+            let _ = write!(self.0, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_output_reaches_the_captured_logs() {
+        // The subscriber the plugin sets globally, for this test only
+        let logs = CapturedLogs::default();
+        let output = LogOutput::default();
+        let printed = Arc::new(Mutex::new(String::new()));
+        let sink = printed.clone();
+        output.redirect(move |record| sink.lock().unwrap().push_str(&record));
+        let subscriber = Registry::default()
+            .with(LevelFilter::INFO)
+            .with(CaptureLayer(Some(logs.clone())))
+            .with(fmt::Layer::default().with_writer(move || output.clone()));
+
+        let lines = bevy::utils::tracing::subscriber::with_default(subscriber, || {
+            let ((), lines) = capture_output(CommandSource::Keybinding, || {
+                info!("captured line");
+                debug!("filtered line");
+            });
+            info!("uncaptured line");
+            lines
+        });
+
+        let messages = lines.iter().map(|line| line.message.as_str()).collect::<Vec<_>>();
+        assert_eq!(messages, vec!["captured line"]);
+        let sources = logs.since(0).into_iter().map(|line| (line.message, line.source)).collect::<Vec<_>>();
+        assert_eq!(sources, vec![
+            ("captured line".to_string(), Some(CommandSource::Keybinding)),
+            ("uncaptured line".to_string(), None),
+        ]);
+
+        // The console that ran the command prints its output
+        let printed = printed.lock().unwrap();
+        assert!(printed.contains("uncaptured line") && !printed.contains(" captured line"), "{}", printed);
+    }
+}
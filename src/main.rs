@@ -1,45 +1,120 @@
 
+mod completion;
+mod confirm;
 mod dev_api;
+mod events;
+mod frontend;
+#[cfg(test)]
+mod harness;
+mod history;
+mod jobs;
+mod keybinds;
+mod log_capture;
+mod middleware;
+mod overlay;
+mod parsers;
+mod permissions;
+mod presets;
+mod remote;
+mod replay;
+mod routing;
+mod rpc;
+mod schema;
+mod snapshot;
+mod suggest;
+mod terminal;
 mod test_commands;
 mod test_tool;
+mod tool_config;
 
-use std::{any::{Any, TypeId}, str::FromStr, sync::Arc};
-use bevy::{app::AppExit, ecs::world::CommandQueue, reflect::GetTypeRegistration};
-use bevy::utils::HashMap;
+use std::{any::{Any, TypeId}, io::IsTerminal, str::FromStr, sync::Arc};
+use bevy::{app::AppExit, ecs::world::CommandQueue, log::LogPlugin, reflect::{GetTypeRegistration, TypeRegistry}};
+use bevy::utils::{HashMap, HashSet};
 use bevy::prelude::*;
 
 
+use completion::CompletionTree;
 use dev_api::*;
-use rustyline::error::ReadlineError;
-use test_commands::{Gold, PrintGold, SetGold};
-
-#[derive(Resource, Deref, DerefMut)]
-struct Console(rustyline::DefaultEditor);
+use events::{CommandSource, DevCommandExecuted, DevCommandFailed, DevCommandParsed, ToolPatched};
+use frontend::{DevConsoleFrontends, DevConsolePlugin};
+use history::{DevHistory, HistoryEntry};
+use keybinds::Keybindings;
+use presets::{ToolPreset, ToolPresets};
+use log_capture::LogLine;
+use overlay::DevConsoleOverlayPlugin;
+use replay::ConsoleRecorder;
+use routing::CommandRoute;
+use terminal::RustylineFrontend;
+use test_commands::{AddGold, BenchmarkSpawn, Gold, PrintGold, SetGold};
 
 fn main() {
-    let rl = rustyline::DefaultEditor::new().unwrap();
-    App::new()
-        .add_plugins(DefaultPlugins)
-        .insert_resource(Console(rl))
-        .add_systems(Update, read_console)
+    let mut app = App::new();
+    app
+        .add_plugins(log_capture::ConsoleLogPlugin::default())
+        .add_plugins(DefaultPlugins.build().disable::<LogPlugin>())
+        .add_plugins(DevConsolePlugin)
+        .add_plugins(DevConsoleOverlayPlugin)
+        .add_plugins(tool_config::ToolConfigPlugin::default())
+        .add_systems(Startup, setup_terminal)
         
         //setup toolbox
         .insert_resource(CLIToolBox::default())
         .add_systems(Startup, setup)
+        .add_systems(Update, log_console_events)
 
         .insert_resource(Gold::default())
+        .register_type::<Gold>();
+
+    // The tool resource is only used by the console, and would link the tool into builds without it
+    if cfg!(feature = "dev_tools") {
+        app.insert_resource(test_tool::DevFlyCamera::default());
+    }
 
-        .insert_resource(test_tool::DevFlyCamera::default())
-        .run();
+    if let Some(remote_console) = remote::RemoteConsolePlugin::from_env() {
+        app.add_plugins(remote_console);
+    }
+
+    // `--record <path>` records the session, `--replay <path>` replays a recorded one from the first frame,
+    // and `--script` runs destructive commands without asking for confirmation
+    let args = std::env::args().collect::<Vec<_>>();
+    if args.iter().any(|arg| arg == "--script") {
+        app.world_mut().resource_mut::<confirm::Confirmations>().script_mode = true;
+    }
+    if let Some(mut recorder) = app.world_mut().get_resource_mut::<ConsoleRecorder>() {
+        for pair in args.windows(2) {
+            match pair[0].as_str() {
+                "--record" => if let Err(err) = recorder.start_recording(&pair[1], 0) {
+                    error!("Failed to start recording to {}: {}", pair[1], err);
+                },
+                "--replay" => if let Err(err) = recorder.start_replay(&pair[1], 0) {
+                    error!("Failed to replay {}: {}", pair[1], err);
+                },
+                _ => {},
+            }
+        }
+    }
+
+    app.run();
 }
 
 fn setup(
     mut toolbox: ResMut<CLIToolBox>,
+    mut presets: ResMut<ToolPresets>,
+    mut keybindings: ResMut<Keybindings>,
 ) {
-    toolbox.add_command::<SetGold>();
-    toolbox.add_command::<PrintGold>();
+    toolbox.add_reversible_command_at::<SetGold>("gold set").unwrap();
+    toolbox.add_command_at::<AddGold>("gold add").unwrap();
+    toolbox.add_command_at::<PrintGold>("gold print").unwrap();
+    toolbox.add_command_at::<BenchmarkSpawn>("benchmark spawn").unwrap();
 
-    toolbox.add_tool::<test_tool::DevFlyCamera>();
+    toolbox.add_tool::<test_tool::DevFlyCamera>().unwrap();
+    presets.add::<test_tool::DevFlyCamera>("slow", ToolPreset::new()
+        .with("movement_speed", Some(1f32))
+        .with("turn_speed", Some(3f32))).unwrap();
+    presets.add::<test_tool::DevFlyCamera>("cinematic", ToolPreset::new()
+        .with("movement_speed", Some(0.5f32))
+        .with("turn_speed", Some(0.5f32))).unwrap();
+    keybindings.bind_tool_toggle::<test_tool::DevFlyCamera>("f1").unwrap();
 
     toolbox.direct_applyer::<u64, _>();
     toolbox.direct_applyer::<bool, _>();
@@ -55,54 +130,102 @@ fn setup(
     }));
 }
 
-fn read_console(
-    world: &mut World
+/// Follow what the console runs, as analytics would.
+fn log_console_events(
+    mut parsed: EventReader<DevCommandParsed>,
+    mut executed: EventReader<DevCommandExecuted>,
+    mut failed: EventReader<DevCommandFailed>,
+    mut patched: EventReader<ToolPatched>,
 ) {
-    world.resource_scope(|world, mut console: Mut<Console>| {
-        let result_input = console.readline("> ");
-
-        let input = match result_input {
-            Ok(input) => input,
-            Err(ReadlineError::Interrupted) => {
-                println!("CTRL-C");
-                world.send_event(AppExit);
-                return;
-            },
-            Err(ReadlineError::Eof) => {
-                println!("CTRL-D");
-                world.send_event(AppExit);
-                return;
-            },
-            Err(err) => {
-                println!("Error: {:?}", err);
-                return;
-            }
-        };
-
-        world.resource_scope(|world, mut toolbox: Mut<CLIToolBox>| {
-            toolbox.parse_input(&input, world);
-        });
-    });
-    
+    for event in parsed.read() {
+        debug!("{:?} parsed {}: {:?}", event.source, event.path, event.command);
+    }
+    for event in executed.read() {
+        debug!("{:?} ran {}: {:?}", event.source, event.path, event.command);
+    }
+    for event in failed.read() {
+        debug!("{:?} failed to run {}: {}", event.source, event.name, event.error);
+    }
+    for event in patched.read() {
+        debug!("{:?} patched {}: {:?}", event.source, event.tool, event.values);
+    }
+}
 
-    
+/// Read commands from the terminal, when one is attached.
+fn setup_terminal(
+    mut frontends: ResMut<DevConsoleFrontends>,
+    log_output: Option<Res<log_capture::LogOutput>>,
+) {
+    if !cfg!(feature = "dev_tools") || !std::io::stdin().is_terminal() {
+        return;
+    }
+    match RustylineFrontend::spawn(log_output.as_deref()) {
+        Ok(frontend) => {
+            frontends.add(frontend);
+        },
+        Err(err) => error!("Failed to open the terminal console: {:?}", err),
+    }
 }
 
+/// Commands handled by the toolbox itself rather than registered as dev commands.
+const BUILTINS: &[&str] = &["help", "exit", "dump-schema", "record", "replay", "undo", "redo", "snapshot", "tool", "tools", "toggle", "bind", "unbind", "binds", "sv_cheats", "jobs", "cancel", "log"];
+
+/// Applies a patch to a tool, returning the previous values of the patched fields.
+pub type ToolInsertFn = fn(&mut World, HashMap<String, Box<dyn Reflect>>) -> Result<HashMap<String, Box<dyn Reflect>>, DevToolParseError>;
+
 //Examlpe CLI parser
-#[derive(Default, Resource)]
+#[derive(Resource)]
 /// Resource that contains metadata about all of the CLI tools.
+///
+/// Without the `dev_tools` feature, registering commands and tools does nothing and the toolbox stays empty.
 pub struct CLIToolBox {
-    /// Metadata about all of the available dev commands.
+    /// Metadata about all of the available dev commands, by qualified path.
     pub metadatas: HashMap<String, DevCommandMetadata>,
     pub metadate_create_fn: HashMap<String, fn() -> DevCommandMetadata>,
+    /// The qualified paths of the commands registered under each short path.
+    pub short_paths: HashMap<String, Vec<String>>,
 
+    /// Metadata about all of the available modal dev tools, by qualified name.
     pub tool_metadatas: HashMap<String, DevToolMetaData>,
+    /// The qualified names of the tools registered under each short name.
+    pub tool_short_names: HashMap<String, Vec<String>>,
     pub tool_metadate_create_fn: HashMap<String, fn() -> DevToolMetaData>,
-    pub tool_insert_fn: HashMap<String, fn(&mut World, HashMap<String, Box<dyn Reflect>>) -> Result<(), DevToolParseError>>,
+    /// Apply a patch to a tool, returning the previous values of the patched fields.
+    pub tool_insert_fn: HashMap<String, ToolInsertFn>,
     pub get_tool_fn: HashMap<String, fn(&World) -> &dyn Reflect>,
+    /// Enable or disable a tool, returning whether it is now enabled.
+    pub tool_toggle_fn: HashMap<String, fn(&mut World) -> bool>,
 
     pub apply_from_string: Vec<Box<dyn Fn(&mut dyn Reflect, &str) -> bool + Send + Sync>>,
     pub same_from_string_fn: HashMap<&'static str, Box< dyn Fn(&dyn Reflect, &str) -> Result<Box<dyn Reflect>, DevToolParseError> + Send + Sync>>,
+
+    /// The types of every registered command and tool and of their fields, used to deserialize JSON arguments.
+    pub type_registry: TypeRegistry,
+
+    /// Run around every dev command, in order. See [`DevCommandMiddleware`].
+    pub middlewares: Vec<Box<dyn DevCommandMiddleware>>,
+}
+
+impl Default for CLIToolBox {
+    fn default() -> Self {
+        let mut toolbox = Self {
+            metadatas: HashMap::default(),
+            metadate_create_fn: HashMap::default(),
+            short_paths: HashMap::default(),
+            tool_metadatas: HashMap::default(),
+            tool_short_names: HashMap::default(),
+            tool_metadate_create_fn: HashMap::default(),
+            tool_insert_fn: HashMap::default(),
+            get_tool_fn: HashMap::default(),
+            tool_toggle_fn: HashMap::default(),
+            apply_from_string: Vec::new(),
+            same_from_string_fn: HashMap::default(),
+            type_registry: TypeRegistry::default(),
+            middlewares: Vec::new(),
+        };
+        toolbox.add_default_parsers();
+        toolbox
+    }
 }
 
 
@@ -110,55 +233,520 @@ pub struct CLIToolBox {
 /// Parse a command line input into a DevCommand
 impl CLIToolBox {
 
-    pub fn parse_input(&self, s: &str, world: &mut World) {
+    /// Run a line of input with every permission, returning the output it logged instead of sending it to the global logger.
+    pub fn execute(&self, s: &str, world: &mut World) -> Vec<LogLine> {
+        self.execute_as(s, PermissionLevel::Admin, world)
+    }
+
+    /// Run a line of input for a caller at the given permission level, returning the output it logged.
+    pub fn execute_as(&self, s: &str, caller: PermissionLevel, world: &mut World) -> Vec<LogLine> {
+        self.execute_from(s, caller, CommandSource::Code, world)
+    }
+
+    /// Run a line of input for a caller at the given permission level, returning the output it logged.
+    ///
+    /// The line is added to the active recording, if any, and the lifecycle events it sends carry its `source`.
+    pub fn execute_from(&self, s: &str, caller: PermissionLevel, source: CommandSource, world: &mut World) -> Vec<LogLine> {
+        replay::recorded(world, s, |world| {
+            let ((), output) = log_capture::capture_output(source, || {
+                events::with_source(world, source, |world| self.parse_input(s, caller, world))
+            });
+            output
+        })
+    }
+
+    pub fn parse_input(&self, s: &str, caller: PermissionLevel, world: &mut World) {
+        if self.answer_confirmation(s, world) {
+            return;
+        }
+        let (line, yes) = confirm::strip_yes(s);
+        // Bound lines keep their own --yes
+        let bound_words = s.split_whitespace().skip(1).collect::<Vec<&str>>();
+        let s = line.as_str();
         let words = s.split_whitespace().collect::<Vec<&str>>();
-        let name = words[0];
-        if self.metadatas.contains_key(name) {
-            let mut command_queue = CommandQueue::default();
-            let mut commands = Commands::new(&mut command_queue, world);
-            if let Ok((command, metadata)) = self.parse_dev_command(s) {
-                info!("Command: parsed {:?}", command);
-                (metadata.add_self_to_commands_fn)(&mut commands, command.as_ref());
-
-                command_queue.apply(world);
-            } else {
+        let Some(name) = words.first().map(|name| name.to_lowercase()) else {
+            return;
+        };
+        if BUILTINS.contains(&name.as_str()) {
+            if let Err(err) = self.check_permission(&name, permissions::builtin_permission(&name), caller, world) {
+                error!("{}", err);
+                return;
+            }
+        }
+        if name == "help" {
+            self.print_help(&words[1..], world);
+            return;
+        }
+        if name == "sv_cheats" {
+            // Showing whether cheats are enabled is read-only, changing it is not
+            if words.len() > 1 {
+                if let Err(err) = self.check_permission("sv_cheats", PermissionLevel::Admin, caller, world) {
+                    error!("{}", err);
+                    return;
+                }
+            }
+            self.sv_cheats_command(&words[1..], world);
+            return;
+        }
+        if name == "exit" {
+            world.send_event(AppExit);
+            return;
+        }
+        if name == "dump-schema" {
+            self.dump_schema(words.get(1).copied());
+            return;
+        }
+        if name == "record" {
+            self.record_command(&words[1..], world);
+            return;
+        }
+        if name == "replay" {
+            self.replay_command(&words[1..], world);
+            return;
+        }
+        if name == "snapshot" {
+            if words.get(1) == Some(&"load") && !self.confirm("snapshot load", s, caller, yes, world) {
+                return;
+            }
+            self.snapshot_command(&words[1..], world);
+            return;
+        }
+        if name == "toggle" {
+            match words.get(1) {
+                Some(tool) => self.toggle_tool(tool, world),
+                None => error!("Usage: toggle <tool>"),
+            }
+            return;
+        }
+        if matches!(name.as_str(), "bind" | "unbind" | "binds") {
+            self.bind_command(&name, &bound_words, world);
+            return;
+        }
+        if name == "tool" {
+            self.tool_command(&words[1..], world);
+            return;
+        }
+        if name == "tools" {
+            self.tools_command(&words[1..], world);
+            return;
+        }
+        if name == "jobs" {
+            self.jobs_command(world);
+            return;
+        }
+        if name == "cancel" {
+            self.cancel_command(&words[1..], world);
+            return;
+        }
+        if name == "log" {
+            // Viewing logs is read-only, changing what gets logged is not
+            if words.len() > 2 && words[1] == "level" {
+                if let Err(err) = self.check_permission("log level", PermissionLevel::Admin, caller, world) {
+                    error!("{}", err);
+                    return;
+                }
+            }
+            self.log_command(&words[1..], world);
+            return;
+        }
+        if name == "undo" {
+            self.undo(caller, world);
+            return;
+        }
+        if name == "redo" {
+            self.redo(caller, world);
+            return;
+        }
+
+        match (self.find_command(&words), self.find_tool(&name)) {
+            (Ok(Some((qualified_path, _))), _) => {
+                let metadata = &self.metadatas[&qualified_path];
+                if let Err(err) = self.check_permission(&metadata.path, metadata.permission, caller, world) {
+                    error!("{}", err);
+                    events::command_failed(world, &metadata.path, err);
+                    return;
+                }
+                match self.parse_dev_command(s) {
+                    Ok((command, metadata)) => {
+                        if metadata.destructive && !self.confirm(&metadata.path, s, caller, yes, world) {
+                            return;
+                        }
+                        info!("Command: parsed {:?}", command);
+                        let source = events::current_source(world);
+                        events::send_lifecycle_event(world, DevCommandParsed {
+                            path: qualified_path,
+                            command: command.clone_value(),
+                            source,
+                        });
+                        self.run_dev_command(command.as_ref(), &metadata, world);
+                    },
+                    Err(err) => {
+                        error!("Invalid command: {:?}", s);
+                        events::command_failed(world, &metadata.path, err);
+                    },
+                }
+            },
+            (Ok(None), Ok(Some(qualified_name))) => {
+                let metadata = &self.tool_metadatas[&qualified_name];
+                if let Err(err) = self.check_permission(&name, metadata.permission, caller, world) {
+                    error!("{}", err);
+                    events::command_failed(world, &name, err);
+                    return;
+                }
+                if let Err(err) = self.update_tool_command(s, world) {
+                    error!("Invalid tool update: {:?}", s);
+                    events::command_failed(world, &name, err);
+                }
+            },
+            (Ok(None), Ok(None)) => {
+                if let Some((group, len)) = self.find_group(&words) {
+                    if len < words.len() {
+                        let subcommands = self.subcommands(&group);
+                        let err = DevToolParseError::UnknownCommand {
+                            name: format!("{} {}", group, words[len]),
+                            suggestions: suggest::suggest(words[len], subcommands.iter().map(|subcommand| subcommand.as_str()))
+                                .into_iter()
+                                .map(|subcommand| format!("{} {}", group, subcommand))
+                                .collect(),
+                        };
+                        error!("{}", err);
+                        events::command_failed(world, &words[..=len].join(" "), err);
+                    }
+                    self.print_help(&words[..len], world);
+                } else {
+                    let err = self.unknown_command_error(&name);
+                    error!("{}", err);
+                    events::command_failed(world, &name, err);
+                }
+            },
+            // The ambiguity has already been reported with its candidates
+            (Err(err), _) | (Ok(None), Err(err)) => {
                 error!("Invalid command: {:?}", s);
+                events::command_failed(world, &name, err);
+            },
+        }
+    }
+
+    pub fn add_command<T : DevCommand>(&mut self) -> Result<(), DevToolParseError> {
+        if !cfg!(feature = "dev_tools") {
+            return Ok(());
+        }
+        self.add_command_at::<T>(T::name())
+    }
+
+    /// Add a command under a group path, such as `gold set`.
+    ///
+    /// The words of the path are typed in order to call the command, and each prefix of the path
+    /// is a group that lists its subcommands in `help` and completion.
+    ///
+    /// The command can always be called by its path qualified with the module of its type, such as
+    /// `mygame::economy::gold set`; the short path works as long as no other command shares it.
+    /// Returns an error if a command is already registered under the same qualified path, if the path starts
+    /// with the name of a builtin such as `help`, or if it is the name of a tool.
+    ///
+    /// Without the `dev_tools` feature this does nothing, so the command is not linked into the binary.
+    pub fn add_command_at<T : DevCommand>(&mut self, path: &str) -> Result<(), DevToolParseError> {
+        if !cfg!(feature = "dev_tools") {
+            return Ok(());
+        }
+        self.insert_command::<T>(path, || T::metadata())
+    }
+
+    /// Add a command that can be reverted with `undo`, under a group path like [`add_command_at`](Self::add_command_at).
+    pub fn add_reversible_command_at<T : ReversibleDevCommand>(&mut self, path: &str) -> Result<(), DevToolParseError> {
+        if !cfg!(feature = "dev_tools") {
+            return Ok(());
+        }
+        self.insert_command::<T>(path, || T::reversible_metadata())
+    }
+
+    fn insert_command<T : DevCommand>(&mut self, path: &str, create_fn: fn() -> DevCommandMetadata) -> Result<(), DevToolParseError> {
+        let mut metadata = create_fn();
+        metadata.path = path.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+        let qualified_path = metadata.qualified_path();
+        // A misspelled argument would never be required nor get its default
+        let fields = metadata.argument_names();
+        if let Some((name, _)) = metadata.arguments.iter().find(|(name, _)| !fields.iter().any(|field| field == name)) {
+            error!("Invalid arguments for {}", qualified_path);
+            return Err(unknown_field_error(name, fields.iter().map(|field| field.as_str())));
+        }
+        if self.metadatas.contains_key(&qualified_path) {
+            error!("Command already registered: {}", qualified_path);
+            return Err(DevToolParseError::NameCollision(qualified_path));
+        }
+        // Builtins are matched on the first word and tools on their name before commands, which would hide the command
+        if metadata.path.split(' ').next().is_some_and(|word| BUILTINS.contains(&word)) {
+            error!("Command {} is hidden by the builtin of the same name", qualified_path);
+            return Err(DevToolParseError::NameCollision(metadata.path));
+        }
+        if self.tool_short_names.contains_key(&metadata.path) {
+            error!("Command {} has the name of a tool", qualified_path);
+            return Err(DevToolParseError::NameCollision(metadata.path));
+        }
+        info!("Added command: {}", qualified_path);
+
+        self.type_registry.register::<T>();
+        self.short_paths.entry(metadata.path.clone()).or_default().push(qualified_path.clone());
+        self.metadate_create_fn.insert(qualified_path.clone(), create_fn);
+        self.metadatas.insert(qualified_path, metadata);
+        Ok(())
+    }
+
+    /// Find the command named by the leading words of the input.
+    ///
+    /// Returns the qualified path of the command and the number of words it spans. The longest matching path wins.
+    pub fn find_command(&self, words: &[&str]) -> Result<Option<(String, usize)>, DevToolParseError> {
+        for len in (1..=words.len()).rev() {
+            let path = words[..len].join(" ").to_lowercase();
+            if let Some(qualified_path) = resolve_name(&self.metadatas, &self.short_paths, &path)? {
+                return Ok(Some((qualified_path, len)));
             }
-        } else if self.tool_metadatas.contains_key(name) {
-            if let Err(_) = self.update_tool_command(s, world) {
-                error!("Invalid tool update: {:?}", s);
+        }
+        Ok(None)
+    }
+
+    /// Find the tool with the given short or qualified name, returning its qualified name.
+    pub fn find_tool(&self, name: &str) -> Result<Option<String>, DevToolParseError> {
+        resolve_name(&self.tool_metadatas, &self.tool_short_names, &name.to_lowercase())
+    }
+
+    /// Find the longest group named by the leading words of the input.
+    ///
+    /// Returns the path of the group and the number of words it spans.
+    pub fn find_group(&self, words: &[&str]) -> Option<(String, usize)> {
+        (1..=words.len()).rev().find_map(|len| {
+            let path = words[..len].join(" ").to_lowercase();
+            (!self.subcommands(&path).is_empty()).then_some((path, len))
+        })
+    }
+
+    /// The next words of every command path in the given group, sorted.
+    ///
+    /// An empty group returns the top level of the command tree.
+    pub fn subcommands(&self, group: &str) -> Vec<String> {
+        let mut subcommands = self.short_paths
+            .keys()
+            .filter_map(|path| {
+                if group.is_empty() {
+                    path.split(' ').next()
+                } else {
+                    path.strip_prefix(group)?.strip_prefix(' ')?.split(' ').next()
+                }
+            })
+            .map(|word| word.to_string())
+            .collect::<Vec<_>>();
+        subcommands.sort();
+        subcommands.dedup();
+        subcommands
+    }
+
+    /// Print help for the given path: the top level for an empty path, the subcommands of a group,
+    /// or the usage of a command or tool.
+    ///
+    /// Commands and tools that need more than the read-only level are tagged with their level.
+    pub fn print_help(&self, words: &[&str], world: &World) {
+        let path = words.join(" ").to_lowercase();
+
+        let (Ok(command), Ok(tool)) = (resolve_name(&self.metadatas, &self.short_paths, &path), self.find_tool(&path)) else {
+            return;
+        };
+
+        if let Some(metadata) = command.map(|command| &self.metadatas[&command]) {
+            let destructive = if metadata.destructive { " [destructive]" } else { "" };
+            info!("Usage: {}{}{}", metadata.usage(), permission_tag(metadata.permission, world), destructive);
+            if let Some(description) = metadata.short_description {
+                info!("{}", description);
+            }
+            return;
+        }
+
+        if let Some(metadata) = tool.map(|tool| &self.tool_metadatas[&tool]) {
+            let fields = metadata.field_names()
+                .iter()
+                .map(|field| format!("[--{} <value>]", field))
+                .collect::<Vec<_>>();
+            info!("Usage: {} {}{}", path, fields.join(" "), permission_tag(metadata.permission, world));
+            if let Some(description) = metadata.short_description {
+                info!("{}", description);
+            }
+            return;
+        }
+
+        let subcommands = self.subcommands(&path);
+        if subcommands.is_empty() {
+            error!("{}", self.unknown_command_error(&path));
+            return;
+        }
+
+        info!("Commands{}:", if path.is_empty() { String::new() } else { format!(" in {}", path) });
+        for subcommand in subcommands {
+            let subpath = if path.is_empty() { subcommand.clone() } else { format!("{} {}", path, subcommand) };
+            let (description, tag) = match self.short_paths.get(&subpath).map(|paths| paths.as_slice()) {
+                Some([qualified_path]) => {
+                    let metadata = &self.metadatas[qualified_path];
+                    (metadata.short_description, permission_tag(metadata.permission, world))
+                },
+                Some([_, _, ..]) => (Some("(ambiguous, use a qualified path)"), String::new()),
+                _ => (None, String::new()),
+            };
+            let is_group = !self.subcommands(&subpath).is_empty();
+            match (description, is_group) {
+                (Some(description), _) => info!("  {} - {}{}", subcommand, description, tag),
+                (None, true) => info!("  {} ...", subcommand),
+                (None, false) => info!("  {}{}", subcommand, tag),
+            }
+        }
+
+        if path.is_empty() {
+            let mut tools = self.tool_metadatas.iter().collect::<Vec<_>>();
+            tools.sort_by_key(|(qualified_name, _)| *qualified_name);
+            info!("Tools:");
+            for (qualified_name, metadata) in tools {
+                let short_name = metadata.name.to_lowercase();
+                let name = if self.tool_short_names[&short_name].len() > 1 { qualified_name } else { &short_name };
+                let tag = permission_tag(metadata.permission, world);
+                match metadata.short_description {
+                    Some(description) => info!("  {} - {}{}", name, description, tag),
+                    None => info!("  {}{}", name, tag),
+                }
             }
-        } else {
-            error!("Unknown command/tool: {}", name);
         }
     }
 
-    pub fn add_command<T : DevCommand>(&mut self) {
-        let metadata = T::metadata();
-        info!("Added command: {}", metadata.name);
+    /// Write the JSON schema of every command and tool to a file, or print it when no path is given.
+    pub fn dump_schema(&self, path: Option<&str>) {
+        let schema = serde_json::to_string_pretty(&self.schema()).unwrap();
+        match path {
+            Some(path) => match std::fs::write(path, schema) {
+                Ok(()) => info!("Wrote command schema to {}", path),
+                Err(err) => error!("Failed to write command schema to {}: {}", path, err),
+            },
+            None => info!("{}", schema),
+        }
+    }
 
-        self.metadate_create_fn.insert(metadata.name.to_string().to_lowercase(), || T::metadata());
-        self.metadatas.insert(metadata.name.to_string().to_lowercase(), metadata);
+    /// A snapshot of every command, tool and built-in path with its arguments, for completion.
+    ///
+    /// Commands and tools are listed by their short name, or by their qualified name when the short one is ambiguous.
+    /// The `tool` and `tools` builtins are listed with the tools and presets they take.
+    pub fn completion_tree(&self, world: &World) -> CompletionTree {
+        let mut entries = BUILTINS
+            .iter()
+            .filter(|builtin| !matches!(**builtin, "tool" | "tools"))
+            .map(|builtin| (builtin.to_string(), Vec::new()))
+            .collect::<Vec<_>>();
+        entries.extend(self.metadatas.iter().map(|(qualified_path, metadata)| {
+            let path = if self.short_paths[&metadata.path].len() > 1 { qualified_path } else { &metadata.path };
+            (path.clone(), metadata.argument_names())
+        }));
+
+        let presets = world.get_resource::<ToolPresets>();
+        for (qualified_name, metadata) in self.tool_metadatas.iter() {
+            let name = self.tool_display_name(qualified_name);
+            entries.push((name.clone(), metadata.field_names()));
+            entries.push((format!("tools reset {}", name), Vec::new()));
+            entries.push((format!("tool preset save {}", name), Vec::new()));
+            entries.push((format!("tool preset list {}", name), Vec::new()));
+            let preset_names = presets.map_or(Vec::new(), |presets| presets.names(qualified_name));
+            if preset_names.is_empty() {
+                entries.push((format!("tool preset load {}", name), Vec::new()));
+            }
+            for preset in preset_names {
+                entries.push((format!("tool preset load {} {}", name, preset), Vec::new()));
+            }
+        }
+        CompletionTree { entries }
+    }
+
+    /// The short name of a tool, or its qualified name when the short one is ambiguous.
+    fn tool_display_name(&self, qualified_name: &str) -> String {
+        let short_name = self.tool_metadatas[qualified_name].name.to_lowercase();
+        if self.tool_short_names[&short_name].len() > 1 { qualified_name.to_string() } else { short_name }
+    }
+
+    /// The error for a name that is neither a command, a group nor a tool, suggesting the closest known names.
+    pub fn unknown_command_error(&self, name: &str) -> DevToolParseError {
+        let groups = self.subcommands("");
+        let candidates = self.short_paths.keys()
+            .chain(self.metadatas.keys())
+            .chain(self.tool_short_names.keys())
+            .chain(self.tool_metadatas.keys())
+            .chain(groups.iter())
+            .map(|candidate| candidate.as_str())
+            .chain(BUILTINS.iter().copied());
+        DevToolParseError::UnknownCommand {
+            name: name.to_string(),
+            suggestions: suggest::suggest(name, candidates),
+        }
     }
 
-    pub fn add_tool<T : ModalDevTool>(&mut self) {
+    /// Complete the last word of a partially typed line.
+    pub fn complete(&self, line: &str, world: &World) -> (usize, Vec<String>) {
+        self.completion_tree(world).complete(line)
+    }
+
+    /// Add a modal dev tool, callable by its short name or its name qualified by its module.
+    ///
+    /// Returns an error if a tool is already registered under the same qualified name, or if its short name
+    /// is the name of a builtin or the path of a command.
+    /// Without the `dev_tools` feature this does nothing.
+    pub fn add_tool<T : ModalDevTool>(&mut self) -> Result<(), DevToolParseError> {
+        if !cfg!(feature = "dev_tools") {
+            return Ok(());
+        }
         let metadata = T::metadata();
-        info!("Added tool: {}", metadata.name);
+        let qualified_name = metadata.qualified_name();
+        if self.tool_metadatas.contains_key(&qualified_name) {
+            error!("Tool already registered: {}", qualified_name);
+            return Err(DevToolParseError::NameCollision(qualified_name));
+        }
+        let short_name = metadata.name.to_lowercase();
+        if BUILTINS.contains(&short_name.as_str()) {
+            error!("Tool {} is hidden by the builtin of the same name", qualified_name);
+            return Err(DevToolParseError::NameCollision(short_name));
+        }
+        if self.short_paths.contains_key(&short_name) {
+            error!("Tool {} has the name of a command", qualified_name);
+            return Err(DevToolParseError::NameCollision(short_name));
+        }
+        info!("Added tool: {}", qualified_name);
 
-        self.tool_metadate_create_fn.insert(metadata.name.to_string().to_lowercase(), || T::metadata());
-        self.get_tool_fn.insert(metadata.name.to_string().to_lowercase(), |world| {
+        self.type_registry.register::<T>();
+        self.tool_short_names.entry(short_name).or_default().push(qualified_name.clone());
+        self.tool_metadate_create_fn.insert(qualified_name.clone(), || T::metadata());
+        self.get_tool_fn.insert(qualified_name.clone(), |world| {
             world.get_resource::<T>().unwrap()
         });
-        self.tool_insert_fn.insert(metadata.name.to_string().to_lowercase(), |world, patch| {
+        self.tool_toggle_fn.insert(qualified_name.clone(), |world| {
+            let mut tool = world.resource_mut::<T>();
+            tool.toggle();
+            tool.is_enabled()
+        });
+        self.tool_insert_fn.insert(qualified_name.clone(), |world, patch| {
            let mut tool = world.get_resource_mut::<T>().unwrap();
+           let mut previous = HashMap::new();
            for (k, v) in patch {
                let field = get_field_by_name(tool.as_mut(), &k).unwrap();
+               previous.insert(k.clone(), field.clone_value());
                field.apply(v.as_ref());
                info!("Set {} to {:?}", k, v);
            }
-           Ok(())
+           Ok(previous)
         });
-        self.tool_metadatas.insert(metadata.name.to_string().to_lowercase(), metadata);
+        self.tool_metadatas.insert(qualified_name, metadata);
+        Ok(())
+    }
+
+    /// Enable the tool with the given name if it is disabled, or disable it if it is enabled.
+    pub fn toggle_tool(&self, name: &str, world: &mut World) {
+        match self.find_tool(name) {
+            Ok(Some(qualified_name)) => {
+                let enabled = (self.tool_toggle_fn[&qualified_name])(world);
+                info!("{} {}", name, if enabled { "enabled" } else { "disabled" });
+            },
+            Ok(None) => error!("{}", self.unknown_command_error(name)),
+            Err(_) => {},
+        }
     }
 
     /// Add a direct applyer function to the toolbox.
@@ -250,20 +838,111 @@ impl CLIToolBox {
             return Err(DevToolParseError::InvalidName);
         }
 
-        let name = words[0];
-
         // Look up the metadata for the command
-        let Some(metadata) = self.metadatas.get(&name.to_lowercase()) else {
-            error!("Unknown command: {}", name);
-            return Err(DevToolParseError::InvalidName);
+        let Some((qualified_path, len)) = self.find_command(&words)? else {
+            let err = self.unknown_command_error(words[0]);
+            error!("{}", err);
+            return Err(err);
         };
+        let mut command = self.create_dev_command(&qualified_path)?;
+        let provided = self.parse_reflect_from_cli(&words[len..], &mut command)?;
+        let metadata = self.finish_dev_command(&qualified_path, &provided)?;
 
+        // Return the command and its metadata
+        Ok((command, metadata))
+    }
+
+    /// Create the command registered under `qualified_path`, with its declared default values applied.
+    pub fn create_dev_command(&self, qualified_path: &str) -> Result<Box<dyn Reflect>, DevToolParseError> {
+        let metadata = &self.metadatas[qualified_path];
         let mut command = (metadata.create_default_fn)();
-        
-        self.parse_reflect_from_cli(words, &mut command)?;
 
-        // Return the command and its metadata
-        Ok((command, (self.metadate_create_fn[&metadata.name.to_lowercase()])()))
+        // Fill in the declared default values before reading the arguments
+        for (name, requirement) in metadata.arguments.iter() {
+            if let ArgRequirement::Default(value) = requirement {
+                let field = get_field_by_name(command.as_mut(), name)?;
+                self.apply_value_from_cli(field, value)?;
+            }
+        }
+
+        Ok(command)
+    }
+
+    /// Check that every required argument of a command was supplied, and return its metadata.
+    pub fn finish_dev_command(&self, qualified_path: &str, provided: &HashSet<String>) -> Result<DevCommandMetadata, DevToolParseError> {
+        let metadata = &self.metadatas[qualified_path];
+
+        // Every required argument must have been supplied
+        let missing = metadata.argument_names()
+            .into_iter()
+            .filter(|name| metadata.requirement(name) == ArgRequirement::Required && !provided.contains(name))
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            error!("Missing required arguments: {}. Usage: {}", missing.join(", "), metadata.usage());
+            return Err(DevToolParseError::MissingArguments(missing));
+        }
+
+        let mut created = (self.metadate_create_fn[qualified_path])();
+        created.path = metadata.path.clone();
+        Ok(created)
+    }
+
+    /// Queue a parsed command and apply it to the world.
+    ///
+    /// The command first goes through the middlewares, which can rewrite or veto it. Reversible commands
+    /// add their inverse to the [`DevHistory`]. Commands that run on the server are sent there instead
+    /// when the app is a client, see [`routing::CommandRouting`].
+    pub fn run_dev_command(&self, command: &dyn Reflect, metadata: &DevCommandMetadata, world: &mut World) {
+        let Some(command) = self.before_dev_command(command, metadata, world) else {
+            return;
+        };
+        let route = self.route_dev_command(command.as_ref(), metadata, world);
+        if route == CommandRoute::Failed {
+            let err = DevToolParseError::NotSent(metadata.path.clone());
+            events::command_failed(world, &metadata.path, err.clone());
+            self.after_dev_command(command.as_ref(), metadata, &CommandOutcome::Failed(err), world);
+            return;
+        }
+        if !route.runs_here() {
+            // The server keeps the inverse, the client only keeps the place of the command in its history
+            if route == CommandRoute::Server && metadata.inverse_fn.is_some() {
+                DevHistory::record(world, HistoryEntry::Routed {
+                    path: metadata.qualified_path(),
+                    local: None,
+                    undo: true,
+                });
+            }
+            self.after_dev_command(command.as_ref(), metadata, &CommandOutcome::Sent, world);
+            return;
+        }
+        if let Some(inverse) = self.apply_dev_command(command.as_ref(), metadata, world) {
+            let entry = match route {
+                CommandRoute::Both => HistoryEntry::Routed { path: metadata.qualified_path(), local: Some(inverse), undo: true },
+                _ => HistoryEntry::Command { path: metadata.qualified_path(), command: inverse },
+            };
+            DevHistory::record(world, entry);
+        }
+        self.after_dev_command(command.as_ref(), metadata, &CommandOutcome::Applied, world);
+    }
+
+    /// Apply a command to the world, returning its inverse if it is reversible, and send a [`DevCommandExecuted`].
+    fn apply_dev_command(&self, command: &dyn Reflect, metadata: &DevCommandMetadata, world: &mut World) -> Option<Box<dyn Reflect>> {
+        // The inverse is created from the state before the command runs
+        let inverse = metadata.inverse_fn.map(|inverse_fn| inverse_fn(command, world));
+
+        let mut command_queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut command_queue, world);
+        (metadata.add_self_to_commands_fn)(&mut commands, command);
+
+        command_queue.apply(world);
+
+        let source = events::current_source(world);
+        events::send_lifecycle_event(world, DevCommandExecuted {
+            path: metadata.qualified_path(),
+            command: command.clone_value(),
+            source,
+        });
+        inverse
     }
 
     pub fn update_tool_command(&self, s: &str, world: &mut World) -> Result<(), DevToolParseError> {
@@ -277,16 +956,17 @@ impl CLIToolBox {
 
         let name = words[0];
 
-        // Look up the metadata for the command
-        let Some(metadata) = self.tool_metadatas.get(&name.to_lowercase()) else {
-            error!("Unknown tool: {}", name);
-            return Err(DevToolParseError::InvalidName);
+        // Look up the qualified name of the tool
+        let Some(qualified_name) = self.find_tool(name)? else {
+            let err = self.unknown_command_error(name);
+            error!("{}", err);
+            return Err(err);
         };
 
         let mut patch = HashMap::new();
 
         { // create brackets to drop tool reference
-            let mut tool = (self.get_tool_fn[&metadata.name.to_lowercase()])(world);
+            let mut tool = (self.get_tool_fn[&qualified_name])(world);
 
             let mut named_param = None;
 
@@ -295,10 +975,7 @@ impl CLIToolBox {
                     named_param = Some(word.trim_start_matches("--").to_string());
                 } else {
                     if let Some(named_param) = &named_param {
-                        let Ok(field) = get_field_by_name_readonly(tool, named_param) else {
-                            error!("Invalid field: {}", named_param);
-                            return Err(DevToolParseError::InvalidToolData);
-                        };   
+                        let field = get_field_by_name_readonly(tool, named_param)?;
                         let field_type = field.get_represented_type_info().unwrap().type_path();
                         info!("Field type path: {:?}", field_type);
                         if let Some(applyer) = self.same_from_string_fn.get(&field_type) {
@@ -316,80 +993,133 @@ impl CLIToolBox {
             }
         }
 
-        (self.tool_insert_fn[&metadata.name.to_lowercase()])(world, patch)?;
+        self.patch_tool(&qualified_name, patch, world)
+    }
 
-        Ok(())
+    /// Apply a single command line value to a field, using the first applyer that accepts it.
+    fn apply_value_from_cli(&self, field: &mut dyn Reflect, value: &str) -> Result<(), DevToolParseError> {
+        for applyer in self.apply_from_string.iter() {
+            if applyer(field, value) {
+                return Ok(());
+            }
+        }
+        error!("Not found applyer for value: {}", value);
+        Err(DevToolParseError::InvalidToolData)
     }
 
-    fn parse_reflect_from_cli(&self, words: Vec<&str>, target: &mut Box<dyn Reflect>) -> Result<(), DevToolParseError> {
-        let mut named_param = None;
-        let mut is_named_style = false;
+    /// Fill the fields of `target` from the argument words following the command path.
+    ///
+    /// Positional and `--named` arguments can be mixed: a named argument takes exactly one value
+    /// (either `--name value` or `--name=value`), and positional arguments fill the next field that
+    /// was not already supplied by name. Every word after a `--` terminator is positional.
+    ///
+    /// Returns the names of the fields that were supplied.
+    fn parse_reflect_from_cli(&self, words: &[&str], target: &mut Box<dyn Reflect>) -> Result<HashSet<String>, DevToolParseError> {
+        // The names of the fields that received a value
+        let mut provided = HashSet::new();
+        // The named parameter waiting for its value
+        let mut named_param: Option<String> = None;
+        // Whether the `--` terminator was seen
+        let mut only_positional = false;
+        // Index of the next field to try in positional style
         let mut idx = 0;
-        // The current named parameter being parsed
-        // Whether or not we are currently in named style
-        // Index of the next parameter to expect in positional style
-        
-        // Parse all words following the command name
-        for word in words.iter().skip(1) {
+
+        // Parse all argument words
+        for word in words.iter() {
             // Named style parameter
-            if word.starts_with("--") {
-                is_named_style = true;
-                named_param = Some(word.trim_start_matches("--").to_string());
-            } else {
-                // Positional style parameter
-        
-                // Get the field to apply the value to
-                if is_named_style {
-                    // Retrieve the named parameter
-                    let Some(named_param) = &named_param else {
-                        error!("Not found name for value: {}", word);
-                        return Err(DevToolParseError::InvalidToolData);
-                    };
-        
-                    // Find the field with the matching name
-                    let Ok(field) = get_field_by_name(target.as_mut(), named_param) else {
-                        error!("Invalid name: {}", named_param);
-                        return Err(DevToolParseError::InvalidToolData);
-                    };
-        
-                    // Apply the value to the field
-                    let mut ok = false;
-                    for applyer in self.apply_from_string.iter() {
-                        if applyer(field, &word) {
-                            ok = true;
-                            break;
-                        }
-                    }
-                    if !ok {
-                        error!("Not found applyer for value: {}", word);
-                        return Err(DevToolParseError::InvalidToolData);
-                    }
-                } else {
-                    // Find the next field in positional style
-                    let Ok(field) = get_field_by_idx(target.as_mut(), idx) else {
-                        error!("Invalid index: {}", idx);
-                        return Err(DevToolParseError::InvalidToolData);
-                    };
-        
-                    // Apply the value to the field
-                    let mut ok = false;
-                    for applyer in self.apply_from_string.iter() {
-                        if applyer(field, &word) {
-                            ok = true;
-                            break;
-                        }
-                    }
-                    if !ok {
-                        error!("Not found applyer for value: {}", word);
-                        return Err(DevToolParseError::InvalidToolData);
+            if !only_positional && named_param.is_none() {
+                if *word == "--" {
+                    only_positional = true;
+                    continue;
+                }
+                if let Some(name) = word.strip_prefix("--") {
+                    if let Some((name, value)) = name.split_once('=') {
+                        let field = get_field_by_name(target.as_mut(), name)?;
+                        self.apply_value_from_cli(field, value)?;
+                        provided.insert(name.to_string());
+                    } else {
+                        named_param = Some(name.to_string());
                     }
-        
-                    // Increment the index of the next positional style parameter
-                    idx += 1;
+                    continue;
                 }
             }
+
+            // Value of the previous named parameter
+            if let Some(name) = named_param.take() {
+                let field = get_field_by_name(target.as_mut(), &name)?;
+                self.apply_value_from_cli(field, word)?;
+                provided.insert(name);
+                continue;
+            }
+
+            // Positional style parameter, skipping fields that were already supplied by name
+            let name = loop {
+                let Some(name) = get_field_name_by_idx(target.as_ref(), idx) else {
+                    error!("Too many arguments, unexpected value: {}", word);
+                    return Err(DevToolParseError::InvalidToolData);
+                };
+                if !provided.contains(&name) {
+                    break name;
+                }
+                idx += 1;
+            };
+
+            let field = get_field_by_idx(target.as_mut(), idx)?;
+            self.apply_value_from_cli(field, word)?;
+            provided.insert(name);
+
+            // Increment the index of the next positional style parameter
+            idx += 1;
         }
-        Ok(())
+
+        if let Some(name) = named_param {
+            error!("Missing value for argument: --{}", name);
+            return Err(DevToolParseError::InvalidToolData);
+        }
+
+        Ok(provided)
+    }
+}
+
+/// The tag shown in help after a command or tool needing the given level, such as ` [cheat]`.
+fn permission_tag(level: PermissionLevel, world: &World) -> String {
+    match level {
+        PermissionLevel::ReadOnly => String::new(),
+        PermissionLevel::Cheat if !permissions::cheats_enabled(world) => " [cheat, disabled]".to_string(),
+        level => format!(" [{}]", level),
+    }
+}
+
+/// Resolve a name typed by the user to a key of `entries`.
+///
+/// Qualified names are looked up directly, while short names only resolve when a single entry is registered under them.
+fn resolve_name<V>(entries: &HashMap<String, V>, short_names: &HashMap<String, Vec<String>>, name: &str) -> Result<Option<String>, DevToolParseError> {
+    if entries.contains_key(name) {
+        return Ok(Some(name.to_string()));
+    }
+    match short_names.get(name).map(|candidates| candidates.as_slice()) {
+        Some([qualified_name]) => Ok(Some(qualified_name.clone())),
+        Some(candidates) if !candidates.is_empty() => {
+            error!("Ambiguous name {}, could be any of: {}", name, candidates.join(", "));
+            Err(DevToolParseError::AmbiguousName(candidates.to_vec()))
+        },
+        _ => Ok(None),
+    }
+}
+
+/// The name of the field at the given index, or its index for unnamed fields.
+fn get_field_name_by_idx(command: &dyn Reflect, idx: usize) -> Option<String> {
+    match command.reflect_ref() {
+        bevy::reflect::ReflectRef::Struct(r) => r.name_at(idx).map(|name| name.to_string()),
+        bevy::reflect::ReflectRef::TupleStruct(r) => (idx < r.field_len()).then(|| idx.to_string()),
+        bevy::reflect::ReflectRef::Tuple(r) => (idx < r.field_len()).then(|| idx.to_string()),
+        bevy::reflect::ReflectRef::List(r) => (idx < r.len()).then(|| idx.to_string()),
+        bevy::reflect::ReflectRef::Array(r) => (idx < r.len()).then(|| idx.to_string()),
+        bevy::reflect::ReflectRef::Enum(r) => match r.name_at(idx) {
+            Some(name) => Some(name.to_string()),
+            None => (idx < r.field_len()).then(|| idx.to_string()),
+        },
+        bevy::reflect::ReflectRef::Map(_) | bevy::reflect::ReflectRef::Value(_) => None,
     }
 }
 
@@ -449,14 +1179,23 @@ fn get_field_by_idx<'a>(command: &'a mut dyn Reflect, idx: usize) -> Result<&'a
     Ok(field)
 }
 
+/// Report a field name that does not exist, suggesting the closest of the existing field names.
+fn unknown_field_error<'a>(name: &str, field_names: impl Iterator<Item = &'a str>) -> DevToolParseError {
+    let err = DevToolParseError::UnknownField {
+        name: name.to_string(),
+        suggestions: suggest::suggest(name, field_names),
+    };
+    error!("{}", err);
+    err
+}
+
 fn get_field_by_name<'a>(command: &'a mut dyn Reflect, name: &str) -> Result<&'a mut dyn Reflect, DevToolParseError> {
     let field = match command.reflect_mut() {
         bevy::reflect::ReflectMut::Struct(r) => {
-            let Some(field) = r.field_mut(name) else {
-                error!("Invalid name: {}", name);
-                return Err(DevToolParseError::InvalidToolData);
-            };
-            field
+            if r.field(name).is_none() {
+                return Err(unknown_field_error(name, (0..r.field_len()).filter_map(|idx| r.name_at(idx))));
+            }
+            r.field_mut(name).unwrap()
         },
         bevy::reflect::ReflectMut::TupleStruct(r) => {
             error!("Not support named fields in tuple structs: {}", name);
@@ -479,11 +1218,10 @@ fn get_field_by_name<'a>(command: &'a mut dyn Reflect, name: &str) -> Result<&'a
             return Err(DevToolParseError::InvalidToolData);
         },
         bevy::reflect::ReflectMut::Enum(r) => {
-            let Some(field) = r.field_mut(name) else {
-                error!("Invalid name: {}", name);
-                return Err(DevToolParseError::InvalidToolData);
-            };
-            field
+            if r.field(name).is_none() {
+                return Err(unknown_field_error(name, (0..r.field_len()).filter_map(|idx| r.name_at(idx))));
+            }
+            r.field_mut(name).unwrap()
         },
         bevy::reflect::ReflectMut::Value(r) => {
             error!("Not support named fields in values: {}", name);
@@ -498,8 +1236,7 @@ fn get_field_by_name_readonly<'a>(command: &'a dyn Reflect, name: &str) -> Resul
     let field = match command.reflect_ref() {
         bevy::reflect::ReflectRef::Struct(r) => {
             let Some(field) = r.field(name) else {
-                error!("Invalid name: {}", name);
-                return Err(DevToolParseError::InvalidToolData);
+                return Err(unknown_field_error(name, (0..r.field_len()).filter_map(|idx| r.name_at(idx))));
             };
             field
         },
@@ -525,8 +1262,7 @@ fn get_field_by_name_readonly<'a>(command: &'a dyn Reflect, name: &str) -> Resul
         },
         bevy::reflect::ReflectRef::Enum(r) => {
             let Some(field) = r.field(name) else {
-                error!("Invalid name: {}", name);
-                return Err(DevToolParseError::InvalidToolData);
+                return Err(unknown_field_error(name, (0..r.field_len()).filter_map(|idx| r.name_at(idx))));
             };
             field
         },
@@ -536,4 +1272,224 @@ fn get_field_by_name_readonly<'a>(command: &'a dyn Reflect, name: &str) -> Resul
         },
     };
     Ok(field)
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::harness::DevConsoleTestHarness;
+
+    /// Moves the player, with a misspelled argument name.
+    #[derive(Reflect, Debug, Default)]
+    struct Teleport {
+        x: f32,
+    }
+
+    impl bevy::ecs::world::Command for Teleport {
+        fn apply(self, _world: &mut World) {}
+    }
+
+    impl FromStr for Teleport {
+        type Err = DevToolParseError;
+        fn from_str(_s: &str) -> Result<Self, DevToolParseError> {
+            Err(DevToolParseError::InvalidToolData)
+        }
+    }
+
+    impl DevCommand for Teleport {
+        fn short_description() -> Option<&'static str> {
+            None
+        }
+
+        fn arguments() -> Vec<(&'static str, ArgRequirement)> {
+            vec![("xx", ArgRequirement::Required)]
+        }
+    }
+
+    /// The arguments of a command, for testing how they are parsed.
+    #[derive(Reflect, Debug, Default, PartialEq)]
+    struct Arguments {
+        count: u64,
+        name: String,
+        speed: u64,
+    }
+
+    fn parse_arguments(words: &[&str]) -> Result<(Arguments, Vec<String>), DevToolParseError> {
+        let mut toolbox = CLIToolBox::default();
+        toolbox.direct_applyer::<u64, _>();
+        toolbox.direct_applyer::<String, _>();
+        let mut target: Box<dyn Reflect> = Box::new(Arguments::default());
+        let provided = toolbox.parse_reflect_from_cli(words, &mut target)?;
+        let mut provided = provided.into_iter().collect::<Vec<_>>();
+        provided.sort();
+        let arguments = target.downcast::<Arguments>().unwrap();
+        Ok((*arguments, provided))
+    }
+
+    fn arguments(count: u64, name: &str, speed: u64) -> Arguments {
+        Arguments { count, name: name.to_string(), speed }
+    }
+
+    #[test]
+    fn positional_and_named_arguments_are_mixed() {
+        let (parsed, provided) = parse_arguments(&["5", "--speed", "3", "bob"]).unwrap();
+        assert_eq!(parsed, arguments(5, "bob", 3));
+        assert_eq!(provided, vec!["count", "name", "speed"]);
+
+        let (parsed, provided) = parse_arguments(&["--name=bob", "5"]).unwrap();
+        assert_eq!(parsed, arguments(5, "bob", 0));
+        assert_eq!(provided, vec!["count", "name"]);
+    }
+
+    #[test]
+    fn positional_arguments_skip_named_ones() {
+        let (parsed, _) = parse_arguments(&["--count", "2", "bob", "7"]).unwrap();
+        assert_eq!(parsed, arguments(2, "bob", 7));
+
+        let (parsed, _) = parse_arguments(&["--name=bob", "2", "7"]).unwrap();
+        assert_eq!(parsed, arguments(2, "bob", 7));
+    }
+
+    #[test]
+    fn words_after_the_terminator_are_positional() {
+        let (parsed, _) = parse_arguments(&["1", "--", "--speed"]).unwrap();
+        assert_eq!(parsed, arguments(1, "--speed", 0));
+        let (parsed, _) = parse_arguments(&["--", "1", "--", "4"]).unwrap();
+        assert_eq!(parsed, arguments(1, "--", 4));
+    }
+
+    #[test]
+    fn invalid_arguments_are_rejected() {
+        assert!(parse_arguments(&["1", "--speed"]).is_err());
+        assert!(parse_arguments(&["1", "bob", "2", "3"]).is_err());
+        assert!(matches!(parse_arguments(&["--sped=2"]), Err(DevToolParseError::UnknownField { .. })));
+    }
+
+    /// Sets the gold of another economy, registered under the same path as [`SetGold`].
+    #[derive(Reflect, Debug, Default)]
+    struct OtherSetGold {
+        amount: u64,
+    }
+
+    impl bevy::ecs::world::Command for OtherSetGold {
+        fn apply(self, _world: &mut World) {}
+    }
+
+    impl FromStr for OtherSetGold {
+        type Err = DevToolParseError;
+        fn from_str(_s: &str) -> Result<Self, DevToolParseError> {
+            Err(DevToolParseError::InvalidToolData)
+        }
+    }
+
+    impl DevCommand for OtherSetGold {
+        fn short_description() -> Option<&'static str> {
+            None
+        }
+    }
+
+    /// Another tool with the short name of [`test_tool::DevFlyCamera`].
+    #[derive(Resource, Reflect, Debug, Default)]
+    struct DevFlyCamera {
+        enabled: bool,
+    }
+
+    impl ModalDevTool for DevFlyCamera {
+        fn set_enabled(&mut self, enabled: bool) {
+            self.enabled = enabled;
+        }
+
+        fn is_enabled(&self) -> bool {
+            self.enabled
+        }
+    }
+
+    impl FromStr for DevFlyCamera {
+        type Err = DevToolParseError;
+        fn from_str(_s: &str) -> Result<Self, DevToolParseError> {
+            Err(DevToolParseError::InvalidToolData)
+        }
+    }
+
+    /// A name qualified by a module of this crate, such as `bevy_dev_cli_prototype::test_commands::gold set`.
+    fn qualified(module: &str, name: &str) -> String {
+        format!("{}::{}::{}", env!("CARGO_CRATE_NAME").to_lowercase(), module, name)
+    }
+
+    #[test]
+    fn names_resolve_to_qualified_paths() {
+        let mut harness = DevConsoleTestHarness::new();
+        harness.insert_resource(test_tool::DevFlyCamera::default());
+        harness.add_command_at::<SetGold>("gold set").unwrap();
+        harness.add_tool::<test_tool::DevFlyCamera>().unwrap();
+        let toolbox = harness.toolbox();
+
+        let gold_set = qualified("test_commands", "gold set");
+        assert_eq!(toolbox.find_command(&["gold", "set", "5"]).unwrap(), Some((gold_set.clone(), 2)));
+        assert_eq!(toolbox.find_command(&["GOLD", "Set"]).unwrap(), Some((gold_set.clone(), 2)));
+        let words = gold_set.split(' ').chain(["5"]).collect::<Vec<_>>();
+        assert_eq!(toolbox.find_command(&words).unwrap(), Some((gold_set, 2)));
+        assert_eq!(toolbox.find_command(&["gold"]).unwrap(), None);
+
+        let camera = qualified("test_tool", "devflycamera");
+        assert_eq!(toolbox.find_tool("DevFlyCamera").unwrap(), Some(camera.clone()));
+        assert_eq!(toolbox.find_tool(&camera).unwrap(), Some(camera));
+        assert_eq!(toolbox.find_tool("camera").unwrap(), None);
+    }
+
+    #[test]
+    fn shared_short_names_are_ambiguous() {
+        let mut harness = DevConsoleTestHarness::new();
+        harness.insert_resource(test_tool::DevFlyCamera::default());
+        harness.insert_resource(DevFlyCamera::default());
+        harness.add_command_at::<SetGold>("gold set").unwrap();
+        harness.add_command_at::<OtherSetGold>("gold set").unwrap();
+        harness.add_tool::<test_tool::DevFlyCamera>().unwrap();
+        harness.add_tool::<DevFlyCamera>().unwrap();
+        let toolbox = harness.toolbox();
+
+        let (gold_set, other_gold_set) = (qualified("test_commands", "gold set"), qualified("tests", "gold set"));
+        let Err(DevToolParseError::AmbiguousName(mut candidates)) = toolbox.find_command(&["gold", "set"]) else {
+            panic!("the short path resolved to one command");
+        };
+        candidates.sort();
+        assert_eq!(candidates, vec![gold_set, other_gold_set.clone()]);
+        let words = other_gold_set.split(' ').collect::<Vec<_>>();
+        assert_eq!(toolbox.find_command(&words).unwrap(), Some((other_gold_set, 2)));
+
+        let Err(DevToolParseError::AmbiguousName(candidates)) = toolbox.find_tool("devflycamera") else {
+            panic!("the short name resolved to one tool");
+        };
+        assert_eq!(candidates.len(), 2);
+        let camera = qualified("tests", "devflycamera");
+        assert_eq!(toolbox.find_tool(&camera).unwrap(), Some(camera));
+
+        let output = harness.run("gold set 5");
+        assert!(output.has_error("Ambiguous name gold set"), "{:?}", output);
+    }
+
+    #[test]
+    fn misspelled_arguments_are_rejected() {
+        let mut harness = DevConsoleTestHarness::new();
+        let Err(DevToolParseError::UnknownField { name, suggestions }) = harness.add_command::<Teleport>() else {
+            panic!("the misspelled argument was accepted");
+        };
+        assert_eq!(name, "xx");
+        assert_eq!(suggestions, vec!["x".to_string()]);
+        assert!(harness.toolbox().metadatas.is_empty());
+    }
+
+    #[test]
+    fn hidden_names_are_rejected() {
+        let mut harness = DevConsoleTestHarness::new();
+        harness.insert_resource(test_tool::DevFlyCamera::default());
+        assert!(matches!(harness.add_command_at::<SetGold>("log"), Err(DevToolParseError::NameCollision(_))));
+        assert!(matches!(harness.add_command_at::<SetGold>("help gold"), Err(DevToolParseError::NameCollision(_))));
+
+        harness.add_tool::<test_tool::DevFlyCamera>().unwrap();
+        assert!(matches!(harness.add_command_at::<SetGold>("devflycamera"), Err(DevToolParseError::NameCollision(_))));
+
+        let mut harness = DevConsoleTestHarness::new();
+        harness.add_command_at::<SetGold>("devflycamera").unwrap();
+        assert!(matches!(harness.add_tool::<test_tool::DevFlyCamera>(), Err(DevToolParseError::NameCollision(_))));
+    }
+}
@@ -0,0 +1,118 @@
+use bevy::prelude::*;
+
+use crate::dev_api::*;
+use crate::events::command_failed;
+use crate::CLIToolBox;
+
+/// The middleware chain run around every dev command, see [`DevCommandMiddleware`].
+impl CLIToolBox {
+    /// Add a middleware, run after the ones added before it.
+    pub fn add_middleware(&mut self, middleware: impl DevCommandMiddleware) {
+        self.middlewares.push(Box::new(middleware));
+    }
+
+    /// Run the `before` hooks on a copy of `command`, returning the command to run as rewritten by the middlewares.
+    ///
+    /// When a middleware vetoes the command, it and the middlewares before it see the veto in their `after` hooks,
+    /// the veto is reported as a failure and `None` is returned.
+    pub fn before_dev_command(&self, command: &dyn Reflect, metadata: &DevCommandMetadata, world: &mut World) -> Option<Box<dyn Reflect>> {
+        let mut command = command.clone_value();
+        for (idx, middleware) in self.middlewares.iter().enumerate() {
+            let Err(err) = middleware.before(command.as_mut(), metadata, world) else {
+                continue;
+            };
+            error!("{}", err);
+            command_failed(world, &metadata.path, err.clone());
+            let outcome = CommandOutcome::Vetoed(err);
+            for middleware in self.middlewares[..=idx].iter().rev() {
+                middleware.after(command.as_ref(), metadata, &outcome, world);
+            }
+            return None;
+        }
+        Some(command)
+    }
+
+    /// Run the `after` hooks, in the reverse order of the `before` hooks.
+    pub fn after_dev_command(&self, command: &dyn Reflect, metadata: &DevCommandMetadata, outcome: &CommandOutcome, world: &mut World) {
+        for middleware in self.middlewares.iter().rev() {
+            middleware.after(command, metadata, outcome, world);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use bevy::reflect::ReflectMut;
+
+    use super::*;
+    use crate::harness::DevConsoleTestHarness;
+    use crate::test_commands::{Gold, SetGold};
+
+    /// Records its calls, and clamps or vetoes amounts above `max`.
+    struct Limit {
+        name: &'static str,
+        max: u64,
+        veto: bool,
+        calls: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl DevCommandMiddleware for Limit {
+        fn before(&self, command: &mut dyn Reflect, metadata: &DevCommandMetadata, _world: &mut World) -> Result<(), DevToolParseError> {
+            let ReflectMut::Struct(command) = command.reflect_mut() else {
+                return Ok(());
+            };
+            let amount = command.field_mut("amount").unwrap().downcast_mut::<u64>().unwrap();
+            self.calls.lock().unwrap().push(format!("before {} {}", self.name, amount));
+            if *amount > self.max {
+                if self.veto {
+                    return Err(DevToolParseError::Vetoed { name: metadata.path.clone(), reason: format!("more than {}", self.max) });
+                }
+                *amount = self.max;
+            }
+            Ok(())
+        }
+
+        fn after(&self, _command: &dyn Reflect, _metadata: &DevCommandMetadata, outcome: &CommandOutcome, _world: &mut World) {
+            let outcome = match outcome {
+                CommandOutcome::Applied => "applied",
+                CommandOutcome::Sent => "sent",
+                CommandOutcome::Vetoed(_) => "vetoed",
+                CommandOutcome::Failed(_) => "failed",
+            };
+            self.calls.lock().unwrap().push(format!("after {} {}", self.name, outcome));
+        }
+    }
+
+    fn harness(middlewares: [(&'static str, u64, bool); 2]) -> (DevConsoleTestHarness, Arc<Mutex<Vec<String>>>) {
+        let mut harness = DevConsoleTestHarness::new();
+        harness.insert_resource(Gold(1));
+        harness.toolbox_mut().direct_applyer::<u64, _>();
+        harness.add_command_at::<SetGold>("gold set").unwrap();
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        for (name, max, veto) in middlewares {
+            harness.toolbox_mut().add_middleware(Limit { name, max, veto, calls: calls.clone() });
+        }
+        (harness, calls)
+    }
+
+    #[test]
+    fn middlewares_run_in_order_and_rewrite_commands() {
+        let (mut harness, calls) = harness([("clamp", 50, false), ("log", u64::MAX, false)]);
+        assert!(harness.run("gold set 80").is_ok());
+
+        assert_eq!(harness.world().resource::<Gold>().0, 50);
+        assert_eq!(*calls.lock().unwrap(), vec!["before clamp 80", "before log 50", "after log applied", "after clamp applied"]);
+    }
+
+    #[test]
+    fn vetoed_commands_do_not_run() {
+        let (mut harness, calls) = harness([("log", u64::MAX, false), ("veto", 100, true)]);
+        let output = harness.run("gold set 500");
+        assert!(output.has_error("'gold set' was vetoed: more than 100"), "{:?}", output);
+
+        assert_eq!(harness.world().resource::<Gold>().0, 1);
+        assert_eq!(*calls.lock().unwrap(), vec!["before log 500", "before veto 500", "after veto vetoed", "after log vetoed"]);
+    }
+}
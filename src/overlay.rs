@@ -0,0 +1,421 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use bevy::ecs::event::ManualEventReader;
+use bevy::input::keyboard::{Key, KeyboardInput};
+use bevy::input::{ButtonState, InputSystem};
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+
+use crate::completion::CompletionTree;
+use crate::events::CommandSource;
+use crate::frontend::{DevConsoleFrontend, DevConsoleFrontends, DevConsoleSet, FrontendId};
+use crate::log_capture::CapturedLogs;
+
+/// An in-game drop-down console, drawn with `bevy_ui`, registered as a [`DevConsoleFrontend`].
+///
+/// The console is opened and closed with [`OverlayConsoleSettings::toggle_key`]. While it is open,
+/// typed characters go to the input line, `Enter` runs it, `Tab` completes the current word,
+/// the arrow keys walk through the history and `Ctrl-C` cancels the most recent job. Command output and log lines appear in the scrollback
+/// when the [`CapturedLogs`] resource is present.
+///
+/// The console takes the keyboard while it is open: keyboard events and `ButtonInput<KeyCode>` are cleared
+/// before the game systems run, so typing does not move the player.
+#[derive(Default)]
+pub struct DevConsoleOverlayPlugin;
+
+impl Plugin for DevConsoleOverlayPlugin {
+    fn build(&self, app: &mut App) {
+        if !cfg!(feature = "dev_tools") {
+            return;
+        }
+        let console = OverlayConsole::default();
+        let frontend = OverlayFrontend(console.io.clone());
+
+        app.init_resource::<OverlayConsoleSettings>()
+            .init_resource::<DevConsoleFrontends>()
+            .insert_resource(console)
+            .add_systems(Startup, spawn_overlay)
+            .add_systems(PreUpdate, handle_overlay_input.after(InputSystem).before(DevConsoleSet::Dispatch))
+            .add_systems(Update, update_overlay_ui);
+        let id = app.world_mut().resource_mut::<DevConsoleFrontends>().add(frontend);
+        app.world_mut().resource_mut::<OverlayConsole>().frontend = Some(id);
+    }
+}
+
+/// Configuration of the overlay console.
+#[derive(Resource)]
+pub struct OverlayConsoleSettings {
+    /// The key that opens and closes the console.
+    pub toggle_key: KeyCode,
+    /// How many lines of scrollback are kept.
+    pub max_scrollback: usize,
+    /// How many lines of scrollback are shown at once.
+    pub visible_lines: usize,
+}
+
+impl Default for OverlayConsoleSettings {
+    fn default() -> Self {
+        OverlayConsoleSettings {
+            toggle_key: KeyCode::Backquote,
+            max_scrollback: 500,
+            visible_lines: 20,
+        }
+    }
+}
+
+/// The state of the overlay console.
+#[derive(Resource, Default)]
+pub struct OverlayConsole {
+    pub open: bool,
+    /// The line being typed.
+    pub input: String,
+    /// Previous output, oldest first.
+    pub scrollback: VecDeque<String>,
+    /// Previously submitted lines, oldest first.
+    pub history: Vec<String>,
+    /// The history entry currently shown in the input, if any.
+    pub history_cursor: Option<usize>,
+    /// Index of the next captured log line to show.
+    pub log_cursor: u64,
+    /// Lines exchanged with the [`OverlayFrontend`].
+    pub io: Arc<Mutex<OverlayIo>>,
+    /// The id of the [`OverlayFrontend`], whose output reaches the scrollback without going through the captured log lines.
+    pub frontend: Option<FrontendId>,
+}
+
+impl OverlayConsole {
+    pub fn push_line(&mut self, line: impl Into<String>) {
+        self.scrollback.push_back(line.into());
+    }
+}
+
+/// Lines passed between the overlay systems and the [`OverlayFrontend`].
+#[derive(Default)]
+pub struct OverlayIo {
+    /// Submitted lines waiting to be run.
+    pub submitted: Vec<String>,
+    /// Output waiting to be added to the scrollback.
+    pub output: Vec<String>,
+    /// The completions sent to the frontend, used when `Tab` is pressed.
+    pub completions: CompletionTree,
+}
+
+/// The [`DevConsoleFrontend`] side of the overlay console.
+pub struct OverlayFrontend(Arc<Mutex<OverlayIo>>);
+
+impl DevConsoleFrontend for OverlayFrontend {
+    fn read_input(&mut self) -> Vec<String> {
+        let mut io = self.0.lock().unwrap();
+        let submitted = std::mem::take(&mut io.submitted);
+        // Echo each line so its output follows it in the scrollback
+        io.output.extend(submitted.iter().map(|line| format!("> {}", line)));
+        submitted
+    }
+
+    fn write_output(&mut self, line: &str) {
+        self.0.lock().unwrap().output.push(line.to_string());
+    }
+
+    fn report_error(&mut self, error: &str) {
+        self.0.lock().unwrap().output.push(format!("error: {}", error));
+    }
+
+    fn update_completions(&mut self, completions: &CompletionTree) {
+        self.0.lock().unwrap().completions = completions.clone();
+    }
+}
+
+/// Marks the root node of the overlay console.
+#[derive(Component)]
+pub struct OverlayRoot;
+
+/// Marks the text showing the scrollback of the overlay console.
+#[derive(Component)]
+pub struct OverlayScrollbackText;
+
+/// Marks the text showing the input line of the overlay console.
+#[derive(Component)]
+pub struct OverlayInputText;
+
+fn spawn_overlay(mut commands: Commands) {
+    let text_style = TextStyle {
+        font_size: 16.,
+        ..default()
+    };
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    display: Display::None,
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(0.),
+                    left: Val::Px(0.),
+                    width: Val::Percent(100.),
+                    height: Val::Percent(40.),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::FlexEnd,
+                    overflow: Overflow::clip(),
+                    padding: UiRect::all(Val::Px(8.)),
+                    ..default()
+                },
+                background_color: Color::srgba(0., 0., 0., 0.8).into(),
+                z_index: ZIndex::Global(i32::MAX),
+                ..default()
+            },
+            OverlayRoot,
+        ))
+        .with_children(|parent| {
+            parent.spawn((TextBundle::from_section("", text_style.clone()), OverlayScrollbackText));
+            parent.spawn((TextBundle::from_section("> ", text_style), OverlayInputText));
+        });
+}
+
+/// Modifier keys that turn typed characters into shortcuts. `AltRight` is left out as it is `AltGr` on
+/// many layouts, which types characters.
+const SHORTCUT_MODIFIERS: [KeyCode; 5] = [KeyCode::ControlLeft, KeyCode::ControlRight, KeyCode::AltLeft, KeyCode::SuperLeft, KeyCode::SuperRight];
+
+fn handle_overlay_input(
+    mut reader: Local<ManualEventReader<KeyboardInput>>,
+    // Tracked from the events, as `ButtonInput` is cleared while the console is open
+    mut modifiers: Local<HashSet<KeyCode>>,
+    mut events: ResMut<Events<KeyboardInput>>,
+    mut keys: ResMut<ButtonInput<KeyCode>>,
+    settings: Res<OverlayConsoleSettings>,
+    mut console: ResMut<OverlayConsole>,
+) {
+    let was_open = console.open;
+    for event in reader.read(&events) {
+        if SHORTCUT_MODIFIERS.contains(&event.key_code) {
+            match event.state {
+                ButtonState::Pressed => modifiers.insert(event.key_code),
+                ButtonState::Released => modifiers.remove(&event.key_code),
+            };
+        }
+        if event.state != ButtonState::Pressed {
+            continue;
+        }
+        if event.key_code == settings.toggle_key {
+            console.open = !console.open;
+            continue;
+        }
+        if !console.open {
+            continue;
+        }
+
+        match &event.logical_key {
+            Key::Enter => {
+                let line = std::mem::take(&mut console.input);
+                console.history_cursor = None;
+                if !line.trim().is_empty() {
+                    console.history.push(line.clone());
+                    console.io.lock().unwrap().submitted.push(line);
+                }
+            },
+            Key::Backspace => {
+                console.input.pop();
+            },
+            Key::Escape => console.open = false,
+            Key::Space => console.input.push(' '),
+            Key::Tab => {
+                let (start, candidates) = console.io.lock().unwrap().completions.complete(&console.input);
+                match candidates.as_slice() {
+                    [] => {},
+                    [candidate] => {
+                        console.input.truncate(start);
+                        console.input.push_str(candidate);
+                        console.input.push(' ');
+                    },
+                    _ => {
+                        let line = candidates.join("  ");
+                        console.push_line(line);
+                    },
+                }
+            },
+            Key::ArrowUp => {
+                if console.history.is_empty() {
+                    continue;
+                }
+                let cursor = match console.history_cursor {
+                    Some(cursor) => cursor.saturating_sub(1),
+                    None => console.history.len() - 1,
+                };
+                console.history_cursor = Some(cursor);
+                console.input = console.history[cursor].clone();
+            },
+            Key::ArrowDown => {
+                match console.history_cursor {
+                    Some(cursor) if cursor + 1 < console.history.len() => {
+                        console.history_cursor = Some(cursor + 1);
+                        console.input = console.history[cursor + 1].clone();
+                    },
+                    _ => {
+                        console.history_cursor = None;
+                        console.input.clear();
+                    },
+                }
+            },
+            // Ctrl-C cancels the most recent job, as in the terminal
+            Key::Character(characters) if characters.as_str() == "c" && (modifiers.contains(&KeyCode::ControlLeft) || modifiers.contains(&KeyCode::ControlRight)) => {
+                console.io.lock().unwrap().submitted.push("cancel".to_string());
+            },
+            // Other shortcuts are not typed into the input
+            Key::Character(_) if !modifiers.is_empty() => {},
+            Key::Character(characters) => {
+                console.input.extend(characters.chars().filter(|c| !c.is_control()));
+            },
+            _ => {},
+        }
+    }
+
+    // Including the key that opened or closed the console
+    if was_open || console.open {
+        events.clear();
+        keys.reset_all();
+    }
+}
+
+fn update_overlay_ui(
+    settings: Res<OverlayConsoleSettings>,
+    logs: Option<Res<CapturedLogs>>,
+    mut console: ResMut<OverlayConsole>,
+    mut root: Query<&mut Style, With<OverlayRoot>>,
+    mut scrollback_text: Query<&mut Text, (With<OverlayScrollbackText>, Without<OverlayInputText>)>,
+    mut input_text: Query<&mut Text, (With<OverlayInputText>, Without<OverlayScrollbackText>)>,
+) {
+    let output = std::mem::take(&mut console.io.lock().unwrap().output);
+    console.scrollback.extend(output);
+
+    if let Some(logs) = logs {
+        let lines = logs.since(console.log_cursor);
+        if let Some(last) = lines.last() {
+            console.log_cursor = last.index + 1;
+        }
+        let own = console.frontend.map(CommandSource::Frontend);
+        for line in lines.into_iter().filter(|line| line.source != own) {
+            console.push_line(line.to_string());
+        }
+    }
+    while console.scrollback.len() > settings.max_scrollback {
+        console.scrollback.pop_front();
+    }
+
+    let display = if console.open { Display::Flex } else { Display::None };
+    for mut style in root.iter_mut() {
+        if style.display != display {
+            style.display = display;
+        }
+    }
+
+    if !console.open {
+        return;
+    }
+
+    let skip = console.scrollback.len().saturating_sub(settings.visible_lines);
+    let scrollback = console.scrollback.iter().skip(skip).cloned().collect::<Vec<_>>().join("\n");
+    for mut text in scrollback_text.iter_mut() {
+        if text.sections[0].value != scrollback {
+            text.sections[0].value = scrollback.clone();
+        }
+    }
+
+    let input = format!("> {}_", console.input);
+    for mut text in input_text.iter_mut() {
+        if text.sections[0].value != input {
+            text.sections[0].value = input.clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::input::InputPlugin;
+    use bevy::input::keyboard::NativeKey;
+
+    use super::*;
+    use crate::frontend::DevConsolePlugin;
+    use crate::CLIToolBox;
+
+    fn press(app: &mut App, key_code: KeyCode, logical_key: Key) {
+        app.world_mut().send_event(KeyboardInput { key_code, logical_key, state: ButtonState::Pressed, window: Entity::PLACEHOLDER });
+        app.update();
+    }
+
+    fn release(app: &mut App, key_code: KeyCode) {
+        app.world_mut().send_event(KeyboardInput {
+            key_code,
+            logical_key: Key::Unidentified(NativeKey::Unidentified),
+            state: ButtonState::Released,
+            window: Entity::PLACEHOLDER,
+        });
+        app.update();
+    }
+
+    fn character(c: &str) -> Key {
+        Key::Character(c.into())
+    }
+
+    #[test]
+    fn typing_in_the_overlay() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, InputPlugin, DevConsoleOverlayPlugin));
+        app.update();
+
+        press(&mut app, KeyCode::Backquote, character("`"));
+        assert!(app.world().resource::<OverlayConsole>().open);
+        assert!(!app.world().resource::<ButtonInput<KeyCode>>().pressed(KeyCode::Backquote));
+
+        press(&mut app, KeyCode::KeyW, character("w"));
+        press(&mut app, KeyCode::Space, Key::Space);
+        press(&mut app, KeyCode::Digit1, character("1"));
+        assert_eq!(app.world().resource::<OverlayConsole>().input, "w 1");
+        // The game does not see the keys typed in the console
+        assert!(!app.world().resource::<ButtonInput<KeyCode>>().pressed(KeyCode::KeyW));
+
+        press(&mut app, KeyCode::ControlLeft, Key::Control);
+        press(&mut app, KeyCode::KeyA, character("a"));
+        press(&mut app, KeyCode::KeyC, character("c"));
+        release(&mut app, KeyCode::ControlLeft);
+        press(&mut app, KeyCode::Backspace, Key::Backspace);
+        assert_eq!(app.world().resource::<OverlayConsole>().input, "w ");
+
+        press(&mut app, KeyCode::Enter, Key::Enter);
+        let console = app.world().resource::<OverlayConsole>();
+        assert_eq!(console.io.lock().unwrap().submitted, vec!["cancel".to_string(), "w ".to_string()]);
+        assert_eq!(console.history, vec!["w ".to_string()]);
+
+        press(&mut app, KeyCode::Backquote, character("`"));
+        press(&mut app, KeyCode::KeyW, character("w"));
+        assert!(!app.world().resource::<OverlayConsole>().open);
+        assert!(app.world().resource::<OverlayConsole>().input.is_empty());
+        assert!(app.world().resource::<ButtonInput<KeyCode>>().pressed(KeyCode::KeyW));
+    }
+
+    fn rendered(app: &mut App) -> (Vec<String>, String) {
+        let world = app.world_mut();
+        let scrollback = world.query_filtered::<&Text, With<OverlayScrollbackText>>().single(world).sections[0].value.clone();
+        let input = world.query_filtered::<&Text, With<OverlayInputText>>().single(world).sections[0].value.clone();
+        (scrollback.lines().map(|line| line.to_string()).collect(), input)
+    }
+
+    #[test]
+    fn output_is_rendered_in_the_overlay() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, InputPlugin, DevConsolePlugin, DevConsoleOverlayPlugin))
+            .insert_resource(CLIToolBox::default());
+        app.update();
+
+        press(&mut app, KeyCode::Backquote, character("`"));
+        for (key_code, c) in [(KeyCode::KeyJ, "j"), (KeyCode::KeyO, "o"), (KeyCode::KeyB, "b"), (KeyCode::KeyS, "s")] {
+            press(&mut app, key_code, character(c));
+        }
+        assert_eq!(rendered(&mut app), (Vec::new(), "> jobs_".to_string()));
+
+        press(&mut app, KeyCode::Enter, Key::Enter);
+        app.update();
+        let (scrollback, input) = rendered(&mut app);
+        assert_eq!(scrollback, vec!["> jobs".to_string(), "No running jobs".to_string()]);
+        assert_eq!(input, "> _");
+    }
+}
@@ -0,0 +1,289 @@
+use std::{sync::Arc, time::Duration};
+use bevy::color::palettes::basic;
+use bevy::prelude::*;
+
+use crate::CLIToolBox;
+
+/// Built-in parsers for math and engine types, registered on every `CLIToolBox`.
+///
+/// Arguments are split on whitespace before they reach a parser, so every format
+/// here is written without spaces: `1,2,3`, `(1,2,3)` and `[1,2,3]` are all accepted for vectors.
+impl CLIToolBox {
+    pub fn add_default_parsers(&mut self) {
+        self.from_parse_fn::<Vec2>(Arc::new(parse_vec2));
+        self.from_parse_fn::<Vec3>(Arc::new(parse_vec3));
+        self.from_parse_fn::<Vec4>(Arc::new(parse_vec4));
+        self.from_parse_fn::<IVec2>(Arc::new(parse_ivec2));
+        self.from_parse_fn::<IVec3>(Arc::new(parse_ivec3));
+        self.from_parse_fn::<IVec4>(Arc::new(parse_ivec4));
+        self.from_parse_fn::<Quat>(Arc::new(parse_quat));
+        self.from_parse_fn::<Color>(Arc::new(parse_color));
+        self.from_parse_fn::<Transform>(Arc::new(parse_transform));
+        self.from_parse_fn::<Duration>(Arc::new(parse_duration));
+    }
+}
+
+/// Split a comma separated list of numbers, optionally wrapped in `()` or `[]`.
+fn parse_list<T: std::str::FromStr>(s: &str) -> Option<Vec<T>> {
+    let s = s.trim();
+    let s = s
+        .strip_prefix('(').and_then(|s| s.strip_suffix(')'))
+        .or_else(|| s.strip_prefix('[').and_then(|s| s.strip_suffix(']')))
+        .unwrap_or(s);
+    s.split(',').map(|part| part.trim().parse().ok()).collect()
+}
+
+/// Parse a `Vec2` from `x,y`.
+pub fn parse_vec2(s: &str) -> Option<Vec2> {
+    match parse_list::<f32>(s)?.as_slice() {
+        [x, y] => Some(Vec2::new(*x, *y)),
+        _ => None,
+    }
+}
+
+/// Parse a `Vec3` from `x,y,z`.
+pub fn parse_vec3(s: &str) -> Option<Vec3> {
+    match parse_list::<f32>(s)?.as_slice() {
+        [x, y, z] => Some(Vec3::new(*x, *y, *z)),
+        _ => None,
+    }
+}
+
+/// Parse a `Vec4` from `x,y,z,w`.
+pub fn parse_vec4(s: &str) -> Option<Vec4> {
+    match parse_list::<f32>(s)?.as_slice() {
+        [x, y, z, w] => Some(Vec4::new(*x, *y, *z, *w)),
+        _ => None,
+    }
+}
+
+/// Parse an `IVec2` from `x,y`.
+pub fn parse_ivec2(s: &str) -> Option<IVec2> {
+    match parse_list::<i32>(s)?.as_slice() {
+        [x, y] => Some(IVec2::new(*x, *y)),
+        _ => None,
+    }
+}
+
+/// Parse an `IVec3` from `x,y,z`.
+pub fn parse_ivec3(s: &str) -> Option<IVec3> {
+    match parse_list::<i32>(s)?.as_slice() {
+        [x, y, z] => Some(IVec3::new(*x, *y, *z)),
+        _ => None,
+    }
+}
+
+/// Parse an `IVec4` from `x,y,z,w`.
+pub fn parse_ivec4(s: &str) -> Option<IVec4> {
+    match parse_list::<i32>(s)?.as_slice() {
+        [x, y, z, w] => Some(IVec4::new(*x, *y, *z, *w)),
+        _ => None,
+    }
+}
+
+/// Parse a `Quat` from euler angles in degrees (`x,y,z`, applied in XYZ order)
+/// or from an axis and an angle in degrees (`x,y,z@angle`).
+pub fn parse_quat(s: &str) -> Option<Quat> {
+    if let Some((axis, angle)) = s.split_once('@') {
+        let axis = parse_vec3(axis)?.try_normalize()?;
+        let angle: f32 = angle.parse().ok()?;
+        return Some(Quat::from_axis_angle(axis, angle.to_radians()));
+    }
+
+    let euler = parse_vec3(s)?;
+    Some(Quat::from_euler(
+        EulerRot::XYZ,
+        euler.x.to_radians(),
+        euler.y.to_radians(),
+        euler.z.to_radians(),
+    ))
+}
+
+/// Parse a `Color` from a hex code (`#ff0000`), a basic color name (`red`),
+/// or a functional notation: `rgb(1,0,0)`, `rgba(1,0,0,0.5)`, `hsl(120,1,0.5)` and `hsla(120,1,0.5,0.5)`.
+pub fn parse_color(s: &str) -> Option<Color> {
+    let s = s.trim();
+    if s.starts_with('#') {
+        return Srgba::hex(s).ok().map(Color::from);
+    }
+
+    if let Some((function, args)) = s.split_once('(') {
+        let args = parse_list::<f32>(args.strip_suffix(')')?)?;
+        return match (function.to_lowercase().as_str(), args.as_slice()) {
+            ("rgb", [r, g, b]) => Some(Color::srgb(*r, *g, *b)),
+            ("rgba", [r, g, b, a]) => Some(Color::srgba(*r, *g, *b, *a)),
+            ("hsl", [h, s, l]) => Some(Color::hsl(*h, *s, *l)),
+            ("hsla", [h, s, l, a]) => Some(Color::hsla(*h, *s, *l, *a)),
+            _ => None,
+        };
+    }
+
+    let named = match s.to_lowercase().as_str() {
+        "none" | "transparent" => return Some(Color::NONE),
+        "aqua" | "cyan" => basic::AQUA,
+        "black" => basic::BLACK,
+        "blue" => basic::BLUE,
+        "fuchsia" | "magenta" => basic::FUCHSIA,
+        "gray" | "grey" => basic::GRAY,
+        "green" => basic::GREEN,
+        "lime" => basic::LIME,
+        "maroon" => basic::MAROON,
+        "navy" => basic::NAVY,
+        "olive" => basic::OLIVE,
+        "purple" => basic::PURPLE,
+        "red" => basic::RED,
+        "silver" => basic::SILVER,
+        "teal" => basic::TEAL,
+        "white" => basic::WHITE,
+        "yellow" => basic::YELLOW,
+        _ => return None,
+    };
+    Some(named.into())
+}
+
+/// Parse a `Transform` shorthand.
+///
+/// A bare vector (`1,2,3`) is a translation. Otherwise the transform is a `;` separated
+/// list of `key:value` parts, where the keys are `t`/`translation`, `r`/`rotation`
+/// (any format accepted by [`parse_quat`]) and `s`/`scale` (a vector or a uniform scalar),
+/// e.g. `t:0,1,0;r:0,90,0;s:2`.
+pub fn parse_transform(s: &str) -> Option<Transform> {
+    if let Some(translation) = parse_vec3(s) {
+        return Some(Transform::from_translation(translation));
+    }
+
+    let mut transform = Transform::default();
+    for part in s.split(';').filter(|part| !part.is_empty()) {
+        let (key, value) = part.split_once(':')?;
+        match key {
+            "t" | "translation" => transform.translation = parse_vec3(value)?,
+            "r" | "rotation" => transform.rotation = parse_quat(value)?,
+            "s" | "scale" => {
+                transform.scale = match value.parse::<f32>() {
+                    Ok(uniform) => Vec3::splat(uniform),
+                    Err(_) => parse_vec3(value)?,
+                }
+            }
+            _ => return None,
+        }
+    }
+    Some(transform)
+}
+
+/// Parse a `Duration` from a number followed by a unit (`h`, `m`, `s`, `ms`, `us` or `ns`).
+///
+/// A number without a unit is read as seconds.
+pub fn parse_duration(s: &str) -> Option<Duration> {
+    let s = s.trim();
+    let split = s.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(s.len());
+    let (value, unit) = s.split_at(split);
+    let value: f64 = value.parse().ok()?;
+    if !value.is_finite() || value < 0. {
+        return None;
+    }
+
+    let seconds = match unit {
+        "h" => value * 3600.,
+        "m" | "min" => value * 60.,
+        "" | "s" => value,
+        "ms" => value / 1_000.,
+        "us" => value / 1_000_000.,
+        "ns" => value / 1_000_000_000.,
+        _ => return None,
+    };
+    // Durations too long to represent are rejected rather than saturated
+    Duration::try_from_secs_f64(seconds).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vectors() {
+        assert_eq!(parse_vec2("1,2"), Some(Vec2::new(1., 2.)));
+        assert_eq!(parse_vec3("(1,2,3)"), Some(Vec3::new(1., 2., 3.)));
+        assert_eq!(parse_vec4("[1,2,3,4]"), Some(Vec4::new(1., 2., 3., 4.)));
+        assert_eq!(parse_ivec2("-1,2"), Some(IVec2::new(-1, 2)));
+        assert_eq!(parse_ivec3("1,2,3"), Some(IVec3::new(1, 2, 3)));
+        assert_eq!(parse_ivec4("(1,2,3,4)"), Some(IVec4::new(1, 2, 3, 4)));
+
+        assert_eq!(parse_vec3("1,2"), None);
+        assert_eq!(parse_ivec2("1.5,2"), None);
+        assert_eq!(parse_vec2("(1,2]"), None);
+    }
+
+    #[test]
+    fn colors() {
+        assert_eq!(parse_color("#ff0000"), Some(Color::srgb(1., 0., 0.)));
+        assert_eq!(parse_color("#00ff0080"), Srgba::hex("00ff0080").ok().map(Color::from));
+        assert_eq!(parse_color("Red"), Some(basic::RED.into()));
+        assert_eq!(parse_color("transparent"), Some(Color::NONE));
+        assert_eq!(parse_color("rgb(0,0,1)"), Some(Color::srgb(0., 0., 1.)));
+        assert_eq!(parse_color("rgba(0,0,1,0.5)"), Some(Color::srgba(0., 0., 1., 0.5)));
+        assert_eq!(parse_color("hsl(120,1,0.5)"), Some(Color::hsl(120., 1., 0.5)));
+        assert_eq!(parse_color("hsla(120,1,0.5,0.5)"), Some(Color::hsla(120., 1., 0.5, 0.5)));
+
+        assert_eq!(parse_color("#xyz"), None);
+        assert_eq!(parse_color("rgb(1,0)"), None);
+        assert_eq!(parse_color("rgb(1,0,0"), None);
+        assert_eq!(parse_color("cmyk(0,0,0,1)"), None);
+        assert_eq!(parse_color("reddish"), None);
+    }
+
+    #[test]
+    fn quats() {
+        let euler = parse_quat("0,90,0").unwrap();
+        assert!(euler.abs_diff_eq(Quat::from_rotation_y(90f32.to_radians()), 1e-6));
+        let euler = parse_quat("90,0,90").unwrap();
+        let expected = Quat::from_euler(EulerRot::XYZ, 90f32.to_radians(), 0., 90f32.to_radians());
+        assert!(euler.abs_diff_eq(expected, 1e-6));
+
+        // The axis is normalized
+        let axis_angle = parse_quat("0,0,2@45").unwrap();
+        assert!(axis_angle.abs_diff_eq(Quat::from_rotation_z(45f32.to_radians()), 1e-6));
+
+        assert_eq!(parse_quat("0,0,0@45"), None);
+        assert_eq!(parse_quat("0,1,0@"), None);
+        assert_eq!(parse_quat("0,90"), None);
+    }
+
+    #[test]
+    fn transforms() {
+        assert_eq!(parse_transform("1,2,3"), Some(Transform::from_xyz(1., 2., 3.)));
+
+        let transform = parse_transform("t:0,1,0;r:0,90,0;s:2").unwrap();
+        assert_eq!(transform.translation, Vec3::Y);
+        assert!(transform.rotation.abs_diff_eq(Quat::from_rotation_y(90f32.to_radians()), 1e-6));
+        assert_eq!(transform.scale, Vec3::splat(2.));
+
+        let transform = parse_transform("scale:1,2,3;translation:4,5,6").unwrap();
+        assert_eq!(transform.translation, Vec3::new(4., 5., 6.));
+        assert_eq!(transform.scale, Vec3::new(1., 2., 3.));
+        assert_eq!(transform.rotation, Quat::IDENTITY);
+
+        assert_eq!(parse_transform("t:1,2"), None);
+        assert_eq!(parse_transform("p:1,2,3"), None);
+        assert_eq!(parse_transform("t=1,2,3"), None);
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("2h"), Some(Duration::from_secs(7200)));
+        assert_eq!(parse_duration("1.5m"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_duration("2min"), Some(Duration::from_secs(120)));
+        assert_eq!(parse_duration("3s"), Some(Duration::from_secs(3)));
+        assert_eq!(parse_duration("3"), Some(Duration::from_secs(3)));
+        assert_eq!(parse_duration("250ms"), Some(Duration::from_millis(250)));
+        assert_eq!(parse_duration("10us"), Some(Duration::from_micros(10)));
+        assert_eq!(parse_duration("100ns"), Some(Duration::from_nanos(100)));
+
+        assert_eq!(parse_duration("-1s"), None);
+        assert_eq!(parse_duration("NaN"), None);
+        assert_eq!(parse_duration("inf"), None);
+        assert_eq!(parse_duration("5d"), None);
+        assert_eq!(parse_duration("s"), None);
+        assert_eq!(parse_duration("100000000000000000000s"), None);
+        assert_eq!(parse_duration("100000000000000000000000h"), None);
+    }
+}
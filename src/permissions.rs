@@ -0,0 +1,123 @@
+use bevy::prelude::*;
+
+use crate::dev_api::*;
+use crate::CLIToolBox;
+
+/// Whether commands and tools at the [`PermissionLevel::Cheat`] level can run, like `sv_cheats`.
+///
+/// Cheats are enabled by default in debug builds. In release builds they are disabled and cannot be enabled.
+/// `sv_cheats` shows the current state to any caller, and `sv_cheats 1` or `sv_cheats 0` changes it at the admin level.
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct Cheats {
+    pub enabled: bool,
+}
+
+impl Default for Cheats {
+    fn default() -> Self {
+        Cheats {
+            enabled: cfg!(debug_assertions),
+        }
+    }
+}
+
+/// Whether cheats are enabled in the world, using the default when the [`Cheats`] resource is missing.
+pub fn cheats_enabled(world: &World) -> bool {
+    world.get_resource::<Cheats>().copied().unwrap_or_default().enabled
+}
+
+/// The level needed to run a builtin.
+pub fn builtin_permission(name: &str) -> PermissionLevel {
+    match name {
+        // Cancelling stops the work of a command, which every console can do with Ctrl-C, even with cheats disabled.
+        // `log` and `sv_cheats` only need the admin level to change the logger or the cheats
+        "help" | "binds" | "jobs" | "log" | "cancel" | "sv_cheats" => PermissionLevel::ReadOnly,
+        "undo" | "redo" | "tool" | "tools" | "toggle" => PermissionLevel::Cheat,
+        _ => PermissionLevel::Admin,
+    }
+}
+
+impl CLIToolBox {
+    /// Check that a caller at the `caller` level can run `name`, which needs the `required` level.
+    ///
+    /// Commands at the cheat level also need cheats to be enabled.
+    pub fn check_permission(&self, name: &str, required: PermissionLevel, caller: PermissionLevel, world: &World) -> Result<(), DevToolParseError> {
+        if caller < required {
+            return Err(DevToolParseError::PermissionDenied { name: name.to_string(), required });
+        }
+        if required == PermissionLevel::Cheat && !cheats_enabled(world) {
+            return Err(DevToolParseError::CheatsDisabled(name.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Handle the `sv_cheats` builtin.
+    pub fn sv_cheats_command(&self, args: &[&str], world: &mut World) {
+        let enabled = match args {
+            [] => {
+                info!("sv_cheats {}", cheats_enabled(world) as u8);
+                return;
+            },
+            ["1" | "true" | "on"] => true,
+            ["0" | "false" | "off"] => false,
+            _ => {
+                error!("Usage: sv_cheats [0|1]");
+                return;
+            },
+        };
+        if enabled && !cfg!(debug_assertions) {
+            error!("Cheats cannot be enabled in release builds");
+            return;
+        }
+        world.insert_resource(Cheats { enabled });
+        info!("sv_cheats {}", enabled as u8);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::harness::DevConsoleTestHarness;
+    use crate::test_commands::{Gold, SetGold};
+
+    fn harness() -> DevConsoleTestHarness {
+        let mut harness = DevConsoleTestHarness::new();
+        harness.insert_resource(Gold(1));
+        harness.toolbox_mut().direct_applyer::<u64, _>();
+        harness.add_command_at::<SetGold>("gold set").unwrap();
+        harness
+    }
+
+    #[test]
+    fn cheats_need_sv_cheats() {
+        let mut harness = harness();
+        assert!(harness.run("sv_cheats 0").is_ok());
+        let output = harness.run_as("gold set 5", PermissionLevel::Cheat);
+        assert!(output.has_error("'gold set' is a cheat, and cheats are disabled"), "{:?}", output);
+        assert_eq!(harness.world().resource::<Gold>().0, 1);
+
+        assert!(harness.run("sv_cheats 1").is_ok());
+        assert!(harness.run_as("gold set 5", PermissionLevel::Cheat).is_ok());
+        assert_eq!(harness.world().resource::<Gold>().0, 5);
+    }
+
+    #[test]
+    fn levels_are_checked() {
+        let mut harness = harness();
+        let output = harness.run_as("gold set 5", PermissionLevel::ReadOnly);
+        assert!(output.has_error("permission denied: 'gold set' needs the cheat level"), "{:?}", output);
+        assert_eq!(harness.world().resource::<Gold>().0, 1);
+
+        for line in ["exit", "snapshot save quick", "sv_cheats 0", "log level bevy debug"] {
+            let output = harness.run_as(line, PermissionLevel::Cheat);
+            assert!(output.has_error("needs the admin level"), "{}: {:?}", line, output);
+        }
+        assert!(cheats_enabled(harness.world()));
+    }
+
+    #[test]
+    fn anyone_can_see_whether_cheats_are_enabled() {
+        let mut harness = harness();
+        let output = harness.run_as("sv_cheats", PermissionLevel::ReadOnly);
+        assert!(output.is_ok() && output.contains("sv_cheats 1"), "{:?}", output);
+    }
+}
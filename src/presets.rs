@@ -0,0 +1,247 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+use bevy::reflect::serde::TypedReflectSerializer;
+use bevy::reflect::ReflectRef;
+use bevy::utils::HashMap;
+use serde_json::{Map, Value};
+
+use crate::dev_api::*;
+use crate::{get_field_by_name_readonly, CLIToolBox};
+
+/// Named sets of field values for modal dev tools, such as `slow` and `cinematic` for a fly camera.
+///
+/// Presets are declared in code with [`ToolPresets::add`], or saved from the current state of a tool with
+/// `tool preset save <tool> <name>`. `tool preset load <tool> <name>` applies a preset as a patch to the tool,
+/// which can be undone like any other patch, and `tool preset list <tool>` lists the presets of a tool.
+/// Saved presets are kept between runs by the [`ToolConfigPlugin`](crate::tool_config::ToolConfigPlugin).
+#[derive(Resource, Default)]
+pub struct ToolPresets {
+    /// The presets of each tool by name, by qualified tool name.
+    presets: HashMap<String, BTreeMap<String, ToolPreset>>,
+}
+
+/// Values for some of the fields of a modal dev tool.
+#[derive(Default)]
+pub struct ToolPreset {
+    fields: HashMap<String, Box<dyn Reflect>>,
+    /// Whether the preset was saved from a tool rather than declared in code.
+    saved: bool,
+}
+
+impl ToolPreset {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set a field of the tool to `value` when the preset is loaded.
+    pub fn with(mut self, field: &str, value: impl Reflect) -> Self {
+        self.fields.insert(field.to_string(), Box::new(value));
+        self
+    }
+
+    /// The patch applying this preset to a tool.
+    fn patch(&self) -> HashMap<String, Box<dyn Reflect>> {
+        self.fields.iter().map(|(field, value)| (field.clone(), value.clone_value())).collect()
+    }
+}
+
+impl ToolPresets {
+    /// Declare a preset for the tool `T`.
+    ///
+    /// Returns an error if a field of the preset does not exist on the tool or has a different type.
+    /// Without the `dev_tools` feature this does nothing, like registering the tool.
+    pub fn add<T: ModalDevTool>(&mut self, name: &str, preset: ToolPreset) -> Result<(), DevToolParseError> {
+        if !cfg!(feature = "dev_tools") {
+            return Ok(());
+        }
+        let default = T::default();
+        for (field_name, value) in preset.fields.iter() {
+            let field = get_field_by_name_readonly(&default, field_name)?;
+            if field.reflect_type_path() != value.reflect_type_path() {
+                error!("Preset {} sets {} to a {}, but it is a {}", name, field_name, value.reflect_type_path(), field.reflect_type_path());
+                return Err(DevToolParseError::InvalidToolData);
+            }
+        }
+        self.presets.entry(T::metadata().qualified_name()).or_default().insert(name.to_lowercase(), preset);
+        Ok(())
+    }
+
+    /// The names of the presets of a tool, sorted.
+    pub fn names(&self, qualified_name: &str) -> Vec<String> {
+        self.presets.get(qualified_name).map_or(Vec::new(), |presets| presets.keys().cloned().collect())
+    }
+}
+
+impl CLIToolBox {
+    /// Handle the `tool` builtin.
+    pub fn tool_command(&self, args: &[&str], world: &mut World) {
+        let (action, name, preset) = match args {
+            ["preset", action @ ("save" | "load"), name, preset] => (*action, *name, Some(preset.to_lowercase())),
+            ["preset", "list", name] => ("list", *name, None),
+            _ => {
+                error!("Usage: tool preset save <tool> <name> | tool preset load <tool> <name> | tool preset list <tool>");
+                return;
+            },
+        };
+        let qualified_name = match self.find_tool(name) {
+            Ok(Some(qualified_name)) => qualified_name,
+            Ok(None) => {
+                error!("{}", self.unknown_command_error(name));
+                return;
+            },
+            Err(_) => return,
+        };
+        if !world.contains_resource::<ToolPresets>() {
+            world.init_resource::<ToolPresets>();
+        }
+
+        match (action, preset) {
+            ("save", Some(preset)) => {
+                let ReflectRef::Struct(tool) = (self.get_tool_fn[&qualified_name])(world).reflect_ref() else {
+                    error!("Only tools with named fields have presets");
+                    return;
+                };
+                let fields = (0..tool.field_len())
+                    .filter_map(|idx| Some((tool.name_at(idx)?.to_string(), tool.field_at(idx)?.clone_value())))
+                    .collect();
+                world.resource_mut::<ToolPresets>().presets
+                    .entry(qualified_name)
+                    .or_default()
+                    .insert(preset.clone(), ToolPreset { fields, saved: true });
+                info!("Saved preset {} of {}", preset, name);
+            },
+            ("load", Some(preset)) => {
+                let presets = world.resource::<ToolPresets>();
+                let Some(patch) = presets.presets.get(&qualified_name).and_then(|presets| presets.get(&preset)).map(ToolPreset::patch) else {
+                    let names = presets.names(&qualified_name);
+                    error!("{}", DevToolParseError::UnknownPreset {
+                        tool: name.to_string(),
+                        name: preset.clone(),
+                        suggestions: crate::suggest::suggest(&preset, names.iter().map(|name| name.as_str())),
+                    });
+                    return;
+                };
+                match self.patch_tool(&qualified_name, patch, world) {
+                    Ok(()) => info!("Loaded preset {} of {}", preset, name),
+                    Err(err) => error!("Failed to load preset {} of {}: {}", preset, name, err),
+                }
+            },
+            _ => {
+                let names = world.resource::<ToolPresets>().names(&qualified_name);
+                if names.is_empty() {
+                    info!("No presets for {}", name);
+                } else {
+                    info!("Presets of {}: {}", name, names.join(", "));
+                }
+            },
+        }
+    }
+
+    /// The field values of the presets saved with `tool preset save`, by qualified tool name and preset name.
+    pub fn preset_config(&self, presets: &ToolPresets) -> Value {
+        let mut tools = Map::new();
+        for (qualified_name, presets) in presets.presets.iter() {
+            let mut saved = Map::new();
+            for (name, preset) in presets.iter().filter(|(_, preset)| preset.saved) {
+                let mut fields = Map::new();
+                for (field_name, value) in preset.fields.iter() {
+                    match serde_json::to_value(TypedReflectSerializer::new(value.as_ref(), &self.type_registry)) {
+                        Ok(value) => {
+                            fields.insert(field_name.clone(), value);
+                        },
+                        Err(err) => error!("Failed to save {} of preset {} of {}: {}", field_name, name, qualified_name, err),
+                    }
+                }
+                saved.insert(name.clone(), Value::Object(fields));
+            }
+            if !saved.is_empty() {
+                tools.insert(qualified_name.clone(), Value::Object(saved));
+            }
+        }
+        Value::Object(tools)
+    }
+
+    /// Add the presets of a configuration created by [`preset_config`](Self::preset_config) to the [`ToolPresets`],
+    /// replacing the presets declared in code under the same names.
+    pub fn apply_preset_config(&self, config: &Value, world: &mut World) {
+        let Value::Object(tools) = config else {
+            error!("The preset configuration must be a map of tool names to their presets");
+            return;
+        };
+        let mut loaded = Vec::new();
+        for (qualified_name, presets) in tools.iter() {
+            let (Some(get_tool_fn), Value::Object(presets)) = (self.get_tool_fn.get(qualified_name), presets) else {
+                warn!("Skipping the presets of unknown tool {}", qualified_name);
+                continue;
+            };
+            let tool = get_tool_fn(world);
+            for (name, fields) in presets.iter() {
+                let Value::Object(fields) = fields else {
+                    warn!("Skipping preset {} of {}: expected a map of fields", name, qualified_name);
+                    continue;
+                };
+                let mut preset = ToolPreset { fields: HashMap::new(), saved: true };
+                for (field_name, value) in fields.iter() {
+                    let field = match get_field_by_name_readonly(tool, field_name) {
+                        Ok(field) => field,
+                        Err(err) => {
+                            warn!("Skipping {} of preset {} of {}: {}", field_name, name, qualified_name, err);
+                            continue;
+                        },
+                    };
+                    match self.deserialize_field(field, value) {
+                        Ok(value) => {
+                            preset.fields.insert(field_name.clone(), value);
+                        },
+                        Err(err) => warn!("Skipping {} of preset {} of {}: {}", field_name, name, qualified_name, err),
+                    }
+                }
+                loaded.push((qualified_name.clone(), name.clone(), preset));
+            }
+        }
+
+        let mut presets = world.get_resource_or_insert_with(ToolPresets::default);
+        for (qualified_name, name, preset) in loaded {
+            presets.presets.entry(qualified_name).or_default().insert(name, preset);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::harness::DevConsoleTestHarness;
+    use crate::test_tool::DevFlyCamera;
+
+    fn harness() -> DevConsoleTestHarness {
+        let mut harness = DevConsoleTestHarness::new();
+        harness.insert_resource(DevFlyCamera::default());
+        harness.add_tool::<DevFlyCamera>().unwrap();
+        harness
+    }
+
+    #[test]
+    fn unknown_presets_suggest_close_names() {
+        let mut harness = harness();
+        harness.world_mut().resource_mut::<ToolPresets>().add::<DevFlyCamera>("slow", ToolPreset::new().with("movement_speed", Some(1.0f32))).unwrap();
+        let output = harness.run("tool preset load devflycamera slo");
+        assert!(output.has_error("devflycamera has no preset 'slo'; did you mean 'slow'?"), "{:?}", output);
+    }
+
+    #[test]
+    fn saved_presets_are_kept_in_the_config() {
+        let mut harness = harness();
+        harness.world_mut().resource_mut::<ToolPresets>().add::<DevFlyCamera>("slow", ToolPreset::new().with("movement_speed", Some(1.0f32))).unwrap();
+        harness.world_mut().resource_mut::<DevFlyCamera>().movement_speed = Some(8.0);
+        assert!(harness.run("tool preset save devflycamera fast").is_ok());
+        let config = harness.toolbox().preset_config(harness.world().resource::<ToolPresets>());
+        // Presets declared in code are declared again on the next run
+        assert!(config.pointer(&format!("/{}/slow", DevFlyCamera::metadata().qualified_name())).is_none());
+
+        let mut restarted = self::harness();
+        restarted.world_mut().resource_scope(|world, toolbox: Mut<CLIToolBox>| toolbox.apply_preset_config(&config, world));
+        assert!(restarted.run("tool preset load devflycamera fast").is_ok());
+        assert_eq!(restarted.world().resource::<DevFlyCamera>().movement_speed, Some(8.0));
+    }
+}
@@ -0,0 +1,534 @@
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc::{sync_channel, SyncSender};
+use std::sync::{Arc, Mutex};
+
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use serde_json::{json, Value};
+
+use crate::dev_api::PermissionLevel;
+use crate::events::{with_source, CommandSource};
+use crate::frontend::{DevConsoleFrontend, DevConsoleFrontends, DevConsoleSet, FrontendId};
+use crate::log_capture::CapturedLogs;
+use crate::CLIToolBox;
+
+/// A console frontend that accepts commands over TCP, for servers running without a terminal.
+///
+/// Clients send one request per line, either as plain text (`gold set 10`) or as a JSON object
+/// (`{"command": "gold set 10"}`). Plain text requests are answered with one line per output line,
+/// errors being prefixed with `error: `; JSON requests are answered with a single
+/// `{"output": [...], "errors": [...]}` line.
+///
+/// JSON objects with a `"jsonrpc"` key are handled as JSON-RPC requests by [`CLIToolBox::handle_rpc`].
+///
+/// Clients that ask for log lines are sent them as they are captured: plain text clients with `logs on`
+/// (`log: <line>` lines), JSON clients with `{"logs": true}` (`{"log": {...}}` lines) and JSON-RPC clients
+/// with the `subscribe_logs` method (`log` notifications). `logs off`, `{"logs": false}` and
+/// `{"params": {"enabled": false}}` stop them.
+///
+/// When a secret is set, clients must first send `auth <secret>` (or `{"auth": "<secret>"}`)
+/// before any other request is accepted, and are disconnected after [`MAX_AUTH_ATTEMPTS`] wrong secrets.
+/// Clients that send lines longer than [`MAX_REQUEST_LENGTH`], or that read their responses too slowly
+/// for [`MAX_QUEUED_MESSAGES`] to stay queued, are disconnected too.
+///
+/// Remote clients run commands at [`RemoteConsolePlugin::permission`], which only allows read-only
+/// commands unless a secret is set.
+pub struct RemoteConsolePlugin {
+    /// The address to listen on. Defaults to a port on localhost, so the console is not reachable from other machines.
+    pub address: SocketAddr,
+    /// The shared secret clients have to send before running commands.
+    pub secret: Option<String>,
+    /// The level the commands of remote clients run at.
+    pub permission: PermissionLevel,
+}
+
+impl Default for RemoteConsolePlugin {
+    fn default() -> Self {
+        RemoteConsolePlugin {
+            address: SocketAddr::from(([127, 0, 0, 1], 7878)),
+            secret: None,
+            permission: PermissionLevel::ReadOnly,
+        }
+    }
+}
+
+impl RemoteConsolePlugin {
+    /// Configure the remote console from the `DEV_CONSOLE_PORT`, `DEV_CONSOLE_SECRET` and `DEV_CONSOLE_PERMISSION`
+    /// environment variables.
+    ///
+    /// Returns `None` when no port is set, so that servers only open the console when asked to.
+    /// Clients are trusted with every command when a secret is set, and only with read-only ones otherwise,
+    /// unless a permission level is set.
+    pub fn from_env() -> Option<Self> {
+        let port = std::env::var("DEV_CONSOLE_PORT").ok()?.parse::<u16>().ok()?;
+        let secret = std::env::var("DEV_CONSOLE_SECRET").ok();
+        let permission = std::env::var("DEV_CONSOLE_PERMISSION")
+            .ok()
+            .and_then(|permission| permission.parse().ok())
+            .unwrap_or(if secret.is_some() { PermissionLevel::Admin } else { PermissionLevel::ReadOnly });
+        Some(RemoteConsolePlugin {
+            address: SocketAddr::from(([127, 0, 0, 1], port)),
+            secret,
+            permission,
+        })
+    }
+}
+
+impl Plugin for RemoteConsolePlugin {
+    fn build(&self, app: &mut App) {
+        if !cfg!(feature = "dev_tools") {
+            return;
+        }
+        let listener = match TcpListener::bind(self.address) {
+            Ok(listener) => listener,
+            Err(err) => {
+                error!("Failed to start the remote console on {}: {}", self.address, err);
+                return;
+            }
+        };
+        info!("Remote console listening on {}", self.address);
+
+        let state = Arc::new(Mutex::new(RemoteState::default()));
+        let secret = self.secret.clone();
+        let accept_state = state.clone();
+        std::thread::spawn(move || accept_connections(listener, accept_state, secret));
+
+        app.init_resource::<DevConsoleFrontends>();
+        let frontend = app.world_mut().resource_mut::<DevConsoleFrontends>().add(RemoteFrontend {
+            state: state.clone(),
+            permission: self.permission,
+            in_flight: VecDeque::new(),
+            output: Vec::new(),
+            errors: Vec::new(),
+        });
+        app.insert_resource(RemoteConsole { state, frontend, log_cursor: 0, permission: self.permission })
+            .add_systems(PreUpdate, handle_remote_rpc.in_set(DevConsoleSet::Dispatch))
+            .add_systems(Update, stream_logs_to_remote);
+    }
+}
+
+/// Identifies a client connection.
+pub type ConnectionId = u64;
+
+/// A request received from a client.
+#[derive(Debug, Clone)]
+pub struct RemoteRequest {
+    pub connection: ConnectionId,
+    pub line: String,
+    /// Whether the request was JSON, and should be answered in JSON.
+    pub json: bool,
+}
+
+/// How many wrong secrets a client can send before it is disconnected.
+pub const MAX_AUTH_ATTEMPTS: u32 = 3;
+
+/// The length in bytes of the longest line a client can send.
+pub const MAX_REQUEST_LENGTH: u64 = 64 * 1024;
+
+/// How many messages can wait to be written to a client before it is disconnected.
+pub const MAX_QUEUED_MESSAGES: usize = 1024;
+
+/// How log lines are sent to a client that asked for them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LogStream {
+    Text,
+    Json,
+    JsonRpc,
+}
+
+struct Connection {
+    /// Used to close the connection.
+    stream: TcpStream,
+    /// Messages for the thread writing to the client.
+    outgoing: SyncSender<String>,
+    authenticated: bool,
+    /// The number of wrong secrets sent.
+    failed_auth: u32,
+    /// Whether the last request of this client was JSON.
+    json: bool,
+    logs: Option<LogStream>,
+}
+
+impl Connection {
+    /// Start the thread writing to the client, so that a client reading slowly never blocks the app.
+    fn new(stream: TcpStream, authenticated: bool) -> std::io::Result<Self> {
+        let mut writer = stream.try_clone()?;
+        let (outgoing, messages) = sync_channel::<String>(MAX_QUEUED_MESSAGES);
+        std::thread::spawn(move || {
+            for message in messages {
+                if writer.write_all(message.as_bytes()).is_err() {
+                    let _ = writer.shutdown(Shutdown::Both);
+                    return;
+                }
+            }
+        });
+        Ok(Connection {
+            stream,
+            outgoing,
+            authenticated,
+            failed_auth: 0,
+            json: false,
+            logs: None,
+        })
+    }
+
+    fn send(&mut self, message: &str) {
+        // The reading thread sees the connection closing and removes it
+        if self.outgoing.try_send(format!("{}\n", message)).is_err() {
+            let _ = self.stream.shutdown(Shutdown::Both);
+        }
+    }
+
+    /// Reply to a request that is not a JSON-RPC request.
+    fn reply(&mut self, message: &str, error: bool) {
+        match (self.json, error) {
+            (true, false) => self.send(&json!({ "output": [message] }).to_string()),
+            (true, true) => self.send(&json!({ "errors": [message] }).to_string()),
+            (false, false) => self.send(message),
+            (false, true) => self.send(&format!("error: {}", message)),
+        }
+    }
+}
+
+/// Compare a secret without the time taken telling how much of the attempt was right.
+fn secrets_match(secret: &str, attempt: &str) -> bool {
+    let (secret, attempt) = (secret.as_bytes(), attempt.as_bytes());
+    let difference = secret.iter().zip(attempt).fold(0, |difference, (a, b)| difference | (a ^ b));
+    difference == 0 && secret.len() == attempt.len()
+}
+
+/// State shared between the network threads and the main thread.
+#[derive(Default)]
+struct RemoteState {
+    connections: HashMap<ConnectionId, Connection>,
+    requests: VecDeque<RemoteRequest>,
+    rpc_requests: VecDeque<(ConnectionId, Value)>,
+    next_connection: ConnectionId,
+}
+
+/// Resource giving the main thread access to the remote console connections.
+#[derive(Resource)]
+pub struct RemoteConsole {
+    state: Arc<Mutex<RemoteState>>,
+    /// The frontend running the lines of every connection.
+    frontend: FrontendId,
+    log_cursor: u64,
+    permission: PermissionLevel,
+}
+
+fn accept_connections(listener: TcpListener, state: Arc<Mutex<RemoteState>>, secret: Option<String>) {
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        let Ok(connection) = stream.try_clone().and_then(|stream| Connection::new(stream, secret.is_none())) else {
+            continue;
+        };
+
+        let id = {
+            let mut state = state.lock().unwrap();
+            let id = state.next_connection;
+            state.next_connection += 1;
+            state.connections.insert(id, connection);
+            id
+        };
+
+        let state = state.clone();
+        let secret = secret.clone();
+        std::thread::spawn(move || read_connection(id, stream, state, secret));
+    }
+}
+
+fn read_connection(id: ConnectionId, stream: TcpStream, state: Arc<Mutex<RemoteState>>, secret: Option<String>) {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        // Reading at most two bytes past the limit keeps the line ending of a line at the limit,
+        // and tells it from a longer one
+        match (&mut reader).take(MAX_REQUEST_LENGTH + 2).read_line(&mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {},
+        }
+        let mut state = state.lock().unwrap();
+        let Some(connection) = state.connections.get_mut(&id) else {
+            break;
+        };
+        if line.trim_end_matches(['\r', '\n']).len() as u64 > MAX_REQUEST_LENGTH {
+            connection.reply("request too long", true);
+            break;
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let (request, auth, logs, json) = if line.starts_with('{') {
+            match serde_json::from_str::<Value>(line) {
+                Ok(value) if value.get("jsonrpc").is_some() => {
+                    connection.json = true;
+                    let rpc_id = value.get("id").cloned().unwrap_or(Value::Null);
+                    if !connection.authenticated {
+                        connection.send(&json!({
+                            "jsonrpc": "2.0", "id": rpc_id, "error": { "code": -32000, "message": "not authenticated" }
+                        }).to_string());
+                    } else if value.get("method").and_then(Value::as_str) == Some("subscribe_logs") {
+                        let enabled = value.pointer("/params/enabled").and_then(Value::as_bool).unwrap_or(true);
+                        connection.logs = enabled.then_some(LogStream::JsonRpc);
+                        connection.send(&json!({ "jsonrpc": "2.0", "id": rpc_id, "result": enabled }).to_string());
+                    } else {
+                        state.rpc_requests.push_back((id, value));
+                    }
+                    continue;
+                },
+                Ok(value) => (
+                    value.get("command").and_then(Value::as_str).map(|command| command.to_string()),
+                    value.get("auth").and_then(Value::as_str).map(|auth| auth.to_string()),
+                    value.get("logs").and_then(Value::as_bool),
+                    true,
+                ),
+                Err(err) => {
+                    connection.send(&json!({ "errors": [format!("invalid request: {}", err)] }).to_string());
+                    continue;
+                }
+            }
+        } else if let Some(auth) = line.strip_prefix("auth ") {
+            (None, Some(auth.to_string()), None, false)
+        } else if let Some(logs) = line.strip_prefix("logs ") {
+            (None, None, Some(logs.trim() == "on"), false)
+        } else {
+            (Some(line.to_string()), None, None, false)
+        };
+        connection.json = json;
+
+        if let Some(auth) = auth {
+            connection.authenticated = secret.as_deref().is_none_or(|secret| secrets_match(secret, &auth));
+            if connection.authenticated {
+                connection.reply("authenticated", false);
+                continue;
+            }
+            connection.failed_auth += 1;
+            connection.reply("authentication failed", true);
+            if connection.failed_auth >= MAX_AUTH_ATTEMPTS {
+                break;
+            }
+            continue;
+        }
+
+        if !connection.authenticated {
+            connection.reply("not authenticated", true);
+            continue;
+        }
+
+        if let Some(logs) = logs {
+            connection.logs = logs.then_some(if json { LogStream::Json } else { LogStream::Text });
+            connection.reply(if logs { "logs on" } else { "logs off" }, false);
+            continue;
+        }
+
+        let Some(request) = request else {
+            connection.send(&json!({ "errors": ["missing 'command'"] }).to_string());
+            continue;
+        };
+
+        state.requests.push_back(RemoteRequest { connection: id, line: request, json });
+    }
+
+    // The writing thread sends the queued messages, then the connection closes
+    state.lock().unwrap().connections.remove(&id);
+}
+
+/// The [`DevConsoleFrontend`] side of the remote console.
+struct RemoteFrontend {
+    state: Arc<Mutex<RemoteState>>,
+    permission: PermissionLevel,
+    /// The requests returned by `read_input` whose output has not been sent yet, oldest first.
+    in_flight: VecDeque<RemoteRequest>,
+    output: Vec<String>,
+    errors: Vec<String>,
+}
+
+impl DevConsoleFrontend for RemoteFrontend {
+    fn read_input(&mut self) -> Vec<String> {
+        let requests = std::mem::take(&mut self.state.lock().unwrap().requests);
+        let lines = requests.iter().map(|request| request.line.clone()).collect();
+        self.in_flight.extend(requests);
+        lines
+    }
+
+    fn write_output(&mut self, line: &str) {
+        self.output.push(line.to_string());
+    }
+
+    fn report_error(&mut self, error: &str) {
+        self.errors.push(error.to_string());
+    }
+
+    fn permission(&self) -> PermissionLevel {
+        self.permission
+    }
+
+    fn finish_command(&mut self) {
+        let output = std::mem::take(&mut self.output);
+        let errors = std::mem::take(&mut self.errors);
+        let Some(request) = self.in_flight.pop_front() else {
+            return;
+        };
+
+        let mut state = self.state.lock().unwrap();
+        let Some(connection) = state.connections.get_mut(&request.connection) else {
+            return;
+        };
+        if request.json {
+            connection.send(&json!({ "output": output, "errors": errors }).to_string());
+        } else {
+            for line in output {
+                connection.send(&line);
+            }
+            for error in errors {
+                connection.send(&format!("error: {}", error));
+            }
+        }
+    }
+}
+
+fn handle_remote_rpc(world: &mut World) {
+    let remote = world.resource::<RemoteConsole>();
+    let (state, permission) = (remote.state.clone(), remote.permission);
+    let requests = std::mem::take(&mut state.lock().unwrap().rpc_requests);
+
+    for (connection, request) in requests {
+        let response = world.resource_scope(|world, toolbox: Mut<CLIToolBox>| {
+            with_source(world, CommandSource::Remote(connection), |world| toolbox.handle_rpc(&request, permission, world))
+        });
+        if let Some(connection) = state.lock().unwrap().connections.get_mut(&connection) {
+            connection.send(&response.to_string());
+        }
+    }
+}
+
+fn stream_logs_to_remote(
+    logs: Option<Res<CapturedLogs>>,
+    mut remote: ResMut<RemoteConsole>,
+) {
+    let Some(logs) = logs else {
+        return;
+    };
+    let lines = logs.since(remote.log_cursor);
+    let Some(last) = lines.last() else {
+        return;
+    };
+    remote.log_cursor = last.index + 1;
+
+    let mut state = remote.state.lock().unwrap();
+    for connection in state.connections.values_mut().filter(|connection| connection.authenticated) {
+        let Some(stream) = connection.logs else {
+            continue;
+        };
+        // The output of remote commands is sent to the connection that ran them
+        let lines = lines.iter().filter(|line| match line.source {
+            Some(CommandSource::Remote(_)) => false,
+            source => source != Some(CommandSource::Frontend(remote.frontend)),
+        });
+        for line in lines {
+            let log = json!({ "level": line.level.to_string(), "target": line.target, "message": line.message });
+            match stream {
+                LogStream::Text => connection.send(&format!("log: {}", line)),
+                LogStream::Json => connection.send(&json!({ "log": log }).to_string()),
+                LogStream::JsonRpc => connection.send(&json!({ "jsonrpc": "2.0", "method": "log", "params": log }).to_string()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Connect a client to a remote console that is not attached to an app.
+    fn connect(secret: Option<&str>) -> (BufReader<TcpStream>, Arc<Mutex<RemoteState>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let state = Arc::new(Mutex::new(RemoteState::default()));
+        let accept_state = state.clone();
+        let secret = secret.map(|secret| secret.to_string());
+        std::thread::spawn(move || accept_connections(listener, accept_state, secret));
+
+        let client = TcpStream::connect(address).unwrap();
+        client.set_read_timeout(Some(std::time::Duration::from_secs(5))).unwrap();
+        (BufReader::new(client), state)
+    }
+
+    fn send(client: &mut BufReader<TcpStream>, line: &str) {
+        client.get_mut().write_all(format!("{}\n", line).as_bytes()).unwrap();
+    }
+
+    fn receive(client: &mut BufReader<TcpStream>) -> String {
+        let mut line = String::new();
+        client.read_line(&mut line).unwrap();
+        line.trim_end().to_string()
+    }
+
+    #[test]
+    fn secrets() {
+        assert!(secrets_match("hunter2", "hunter2"));
+        assert!(!secrets_match("hunter2", "hunter3"));
+        assert!(!secrets_match("hunter2", "hunter"));
+        assert!(!secrets_match("hunter2", "hunter22"));
+        assert!(!secrets_match("hunter2", ""));
+    }
+
+    #[test]
+    fn wrong_secrets_disconnect() {
+        let (mut client, state) = connect(Some("hunter2"));
+        send(&mut client, "gold print");
+        assert_eq!(receive(&mut client), "error: not authenticated");
+        for _ in 0..MAX_AUTH_ATTEMPTS {
+            send(&mut client, "auth hunter3");
+            assert_eq!(receive(&mut client), "error: authentication failed");
+        }
+        assert_eq!(receive(&mut client), "");
+        assert!(state.lock().unwrap().connections.is_empty());
+    }
+
+    #[test]
+    fn long_requests_disconnect() {
+        let (mut client, _) = connect(None);
+        // The line ending does not count
+        send(&mut client, &format!("logs on{}", " ".repeat(MAX_REQUEST_LENGTH as usize - "logs on".len())));
+        assert_eq!(receive(&mut client), "logs on");
+
+        send(&mut client, &"a".repeat(MAX_REQUEST_LENGTH as usize + 1));
+        send(&mut client, "gold print");
+        assert_eq!(receive(&mut client), "error: request too long");
+        assert_eq!(receive(&mut client), "");
+    }
+
+    #[test]
+    fn logs_are_opt_in() {
+        let (mut client, state) = connect(Some("hunter2"));
+        send(&mut client, r#"{"auth": "hunter2"}"#);
+        assert_eq!(receive(&mut client), r#"{"output":["authenticated"]}"#);
+        assert_eq!(state.lock().unwrap().connections.values().next().unwrap().logs, None);
+
+        send(&mut client, r#"{"logs": true}"#);
+        assert_eq!(receive(&mut client), r#"{"output":["logs on"]}"#);
+        assert_eq!(state.lock().unwrap().connections.values().next().unwrap().logs, Some(LogStream::Json));
+
+        send(&mut client, r#"{"jsonrpc": "2.0", "id": 1, "method": "subscribe_logs"}"#);
+        assert_eq!(receive(&mut client), r#"{"id":1,"jsonrpc":"2.0","result":true}"#);
+        assert_eq!(state.lock().unwrap().connections.values().next().unwrap().logs, Some(LogStream::JsonRpc));
+
+        send(&mut client, "logs off");
+        assert_eq!(receive(&mut client), "logs off");
+        assert_eq!(state.lock().unwrap().connections.values().next().unwrap().logs, None);
+
+        // Once the reply to the next line arrives, the request was queued
+        send(&mut client, "gold print");
+        send(&mut client, "logs off");
+        assert_eq!(receive(&mut client), "logs off");
+        assert_eq!(state.lock().unwrap().requests.len(), 1);
+    }
+}
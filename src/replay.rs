@@ -0,0 +1,368 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::time::Instant;
+
+use bevy::core::FrameCount;
+use bevy::prelude::*;
+
+use serde_json::Value;
+
+use crate::dev_api::PermissionLevel;
+use crate::events::{with_source, CommandSource};
+use crate::frontend::DevConsoleSet;
+use crate::log_capture;
+use crate::CLIToolBox;
+
+/// Records the lines run by the toolbox to a file, and replays recorded files at the same frames,
+/// so that a bug found after a series of console commands can be reproduced exactly.
+///
+/// A recording has one line per command, `<frame> <seconds> <command>`, where the frame and the
+/// time are counted from the start of the recording. Lines starting with `#` are comments.
+/// Commands starting with `{` are JSON-RPC requests, see [`CLIToolBox::handle_rpc`], or commands a client
+/// sent to this server, see [`CommandRoutingPlugin`](crate::routing::CommandRoutingPlugin).
+/// Every input is recorded once by the entry point it arrives through, with [`recorded`], so the line of
+/// an `execute` request is not recorded again.
+///
+/// - `record start <path>` starts recording to a file, `record stop` stops it.
+/// - `replay <path>` runs the commands of a recording, each one the same number of frames after
+///   the start of the replay as it was after the start of the recording.
+#[derive(Default)]
+pub struct ConsoleReplayPlugin;
+
+impl Plugin for ConsoleReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ConsoleRecorder>()
+            .add_systems(PreUpdate, replay_recorded_commands.in_set(DevConsoleSet::Replay).before(DevConsoleSet::Dispatch));
+    }
+}
+
+/// A command of a recording.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedCommand {
+    /// The frame the command ran at, counted from the start of the recording.
+    pub frame: u32,
+    /// The time the command ran at in seconds, counted from the start of the recording.
+    pub seconds: f64,
+    pub line: String,
+}
+
+impl RecordedCommand {
+    /// Parse a line of a recording, returning `None` for comments and blank lines.
+    pub fn parse(line: &str) -> Option<Result<Self, String>> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let mut parts = line.splitn(3, ' ');
+        let (Some(frame), Some(seconds), Some(command)) = (parts.next(), parts.next(), parts.next()) else {
+            return Some(Err(format!("expected '<frame> <seconds> <command>', got '{}'", line)));
+        };
+        let (Ok(frame), Ok(seconds)) = (frame.parse(), seconds.parse()) else {
+            return Some(Err(format!("invalid frame or time in '{}'", line)));
+        };
+        Some(Ok(RecordedCommand { frame, seconds, line: command.to_string() }))
+    }
+}
+
+impl std::fmt::Display for RecordedCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {:.3} {}", self.frame, self.seconds, self.line)
+    }
+}
+
+/// An active recording.
+struct Recording {
+    path: String,
+    file: BufWriter<File>,
+    start_frame: u32,
+    start_time: Instant,
+}
+
+/// An active replay.
+struct Replay {
+    path: String,
+    commands: VecDeque<RecordedCommand>,
+    start_frame: u32,
+    start_time: Instant,
+    /// When the last replayed command ran in the recording, in seconds.
+    recorded_seconds: f64,
+}
+
+/// Resource holding the active recording and replay, if any.
+#[derive(Resource, Default)]
+pub struct ConsoleRecorder {
+    recording: Option<Recording>,
+    replay: Option<Replay>,
+    /// How many inputs are running inside [`recorded`], so that nested inputs are not recorded twice.
+    depth: u32,
+}
+
+impl ConsoleRecorder {
+    /// Start recording to the file at `path`, replacing it. Stops any previous recording.
+    pub fn start_recording(&mut self, path: &str, frame: u32) -> std::io::Result<()> {
+        self.stop_recording();
+        let mut file = BufWriter::new(File::create(path)?);
+        writeln!(file, "# frame seconds command")?;
+        file.flush()?;
+        self.recording = Some(Recording {
+            path: path.to_string(),
+            file,
+            start_frame: frame,
+            start_time: Instant::now(),
+        });
+        Ok(())
+    }
+
+    /// Stop recording, returning the path of the recording.
+    pub fn stop_recording(&mut self) -> Option<String> {
+        let mut recording = self.recording.take()?;
+        let _ = recording.file.flush();
+        Some(recording.path)
+    }
+
+    /// Add a line run at the given frame to the recording.
+    ///
+    /// Each line is flushed right away, so the recording survives a crash.
+    pub fn record(&mut self, line: &str, frame: u32) {
+        let Some(recording) = &mut self.recording else {
+            return;
+        };
+        let line = line.trim();
+        // Recording the recorder's own commands would make replays recursive
+        if line.is_empty() || matches!(line.split_whitespace().next(), Some("record" | "replay")) {
+            return;
+        }
+
+        let command = RecordedCommand {
+            frame: frame.wrapping_sub(recording.start_frame),
+            seconds: recording.start_time.elapsed().as_secs_f64(),
+            line: line.to_string(),
+        };
+        let result = writeln!(recording.file, "{}", command).and_then(|_| recording.file.flush());
+        if let Err(err) = result {
+            error!("Failed to write to recording {}: {}", recording.path, err);
+        }
+    }
+
+    /// Replay the recording at `path`, starting at the given frame. Stops any previous replay.
+    ///
+    /// Returns the number of commands in the recording.
+    pub fn start_replay(&mut self, path: &str, frame: u32) -> Result<usize, String> {
+        let contents = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+        let commands = contents
+            .lines()
+            .filter_map(RecordedCommand::parse)
+            .collect::<Result<VecDeque<_>, _>>()?;
+        let len = commands.len();
+        self.replay = Some(Replay {
+            path: path.to_string(),
+            commands,
+            start_frame: frame,
+            start_time: Instant::now(),
+            recorded_seconds: 0.0,
+        });
+        Ok(len)
+    }
+
+    /// Stop replaying, returning the path of the recording.
+    pub fn stop_replay(&mut self) -> Option<String> {
+        self.replay.take().map(|replay| replay.path)
+    }
+
+    /// Take the replayed commands due at the given frame.
+    fn due_commands(&mut self, frame: u32) -> Vec<String> {
+        let Some(replay) = &mut self.replay else {
+            return Vec::new();
+        };
+        let elapsed = frame.wrapping_sub(replay.start_frame);
+        let mut due = Vec::new();
+        while replay.commands.front().is_some_and(|command| command.frame <= elapsed) {
+            let command = replay.commands.pop_front().unwrap();
+            replay.recorded_seconds = command.seconds;
+            due.push(command.line);
+        }
+        if replay.commands.is_empty() {
+            // Frames are replayed exactly, a large difference in time means the frame rate differs
+            info!(
+                "Finished replaying {} in {:.3}s, recorded in {:.3}s",
+                replay.path,
+                replay.start_time.elapsed().as_secs_f64(),
+                replay.recorded_seconds,
+            );
+            self.replay = None;
+        }
+        due
+    }
+}
+
+/// Run an input of the toolbox, a console line or a JSON message, adding it to the active recording
+/// unless it is run by another input being recorded.
+pub fn recorded<R>(world: &mut World, line: &str, run: impl FnOnce(&mut World) -> R) -> R {
+    let frame = current_frame(world);
+    let Some(mut recorder) = world.get_resource_mut::<ConsoleRecorder>() else {
+        return run(world);
+    };
+    if recorder.depth == 0 {
+        recorder.record(line, frame);
+    }
+    recorder.depth += 1;
+    let result = run(world);
+    world.resource_mut::<ConsoleRecorder>().depth -= 1;
+    result
+}
+
+/// The current frame, or 0 when frames are not counted.
+pub fn current_frame(world: &World) -> u32 {
+    world.get_resource::<FrameCount>().map_or(0, |frame| frame.0)
+}
+
+impl CLIToolBox {
+    /// Handle the `record` builtin.
+    pub fn record_command(&self, args: &[&str], world: &mut World) {
+        let frame = current_frame(world);
+        let Some(mut recorder) = world.get_resource_mut::<ConsoleRecorder>() else {
+            error!("Recording needs the ConsoleReplayPlugin");
+            return;
+        };
+        match args {
+            ["start", path] => match recorder.start_recording(path, frame) {
+                Ok(()) => info!("Recording commands to {}", path),
+                Err(err) => error!("Failed to start recording to {}: {}", path, err),
+            },
+            ["stop"] => match recorder.stop_recording() {
+                Some(path) => info!("Stopped recording to {}", path),
+                None => warn!("Not recording"),
+            },
+            _ => error!("Usage: record start <path> | record stop"),
+        }
+    }
+
+    /// Handle the `replay` builtin.
+    pub fn replay_command(&self, args: &[&str], world: &mut World) {
+        let frame = current_frame(world);
+        let Some(mut recorder) = world.get_resource_mut::<ConsoleRecorder>() else {
+            error!("Replaying needs the ConsoleReplayPlugin");
+            return;
+        };
+        match args {
+            ["stop"] => match recorder.stop_replay() {
+                Some(path) => info!("Stopped replaying {}", path),
+                None => warn!("Not replaying"),
+            },
+            [path] => match recorder.start_replay(path, frame) {
+                Ok(len) => info!("Replaying {} commands from {}", len, path),
+                Err(err) => error!("Failed to replay {}: {}", path, err),
+            },
+            _ => error!("Usage: replay <path> | replay stop"),
+        }
+    }
+}
+
+/// Run the replayed commands due this frame, logging their output.
+fn replay_recorded_commands(world: &mut World) {
+    let frame = current_frame(world);
+    let due = world.resource_mut::<ConsoleRecorder>().due_commands(frame);
+
+    for line in due {
+        info!("replay> {}", line);
+        if line.starts_with('{') {
+            let message = serde_json::from_str::<Value>(&line).unwrap_or_default();
+            // Requests have a method, commands routed from a client do not
+            if message.get("method").is_some() {
+                let response = world.resource_scope(|world, toolbox: Mut<CLIToolBox>| {
+                    with_source(world, CommandSource::Replay, |world| toolbox.handle_rpc(&message, PermissionLevel::Admin, world))
+                });
+                log_rpc_response(&response);
+            } else {
+                let ((), output) = log_capture::capture_output(CommandSource::Replay, || {
+                    world.resource_scope(|world, toolbox: Mut<CLIToolBox>| {
+                        with_source(world, CommandSource::Replay, |world| toolbox.run_routed_command(&line, PermissionLevel::Admin, world))
+                    })
+                });
+                log_capture::log_lines(output);
+            }
+            continue;
+        }
+        let output = world.resource_scope(|world, toolbox: Mut<CLIToolBox>| {
+            toolbox.execute_from(&line, PermissionLevel::Admin, CommandSource::Replay, world)
+        });
+        log_capture::log_lines(output);
+    }
+}
+
+/// Log the output and the errors of a replayed JSON-RPC request.
+fn log_rpc_response(response: &Value) {
+    if let Some(message) = response.pointer("/error/message").and_then(Value::as_str) {
+        error!("{}", message);
+    }
+    let lines = |key: &str| response.pointer(key).and_then(Value::as_array).cloned().unwrap_or_default();
+    for line in lines("/result/output") {
+        info!("{}", line.as_str().unwrap_or_default());
+    }
+    for line in lines("/result/errors") {
+        error!("{}", line.as_str().unwrap_or_default());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::harness::DevConsoleTestHarness;
+    use crate::test_commands::{Gold, SetGold};
+
+    fn recording_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("{}_{}.txt", name, std::process::id()));
+        path.to_str().unwrap().to_string()
+    }
+
+    fn recorded_lines(path: &str) -> Vec<String> {
+        let contents = std::fs::read_to_string(path).unwrap();
+        contents.lines().filter_map(RecordedCommand::parse).map(|command| command.unwrap().line).collect()
+    }
+
+    #[test]
+    fn inputs_are_recorded_once() {
+        let path = recording_path("nested_recording");
+        let mut harness = DevConsoleTestHarness::new();
+        harness.insert_resource(Gold(0));
+        harness.toolbox_mut().direct_applyer::<u64, _>();
+        harness.add_command_at::<SetGold>("gold set").unwrap();
+
+        harness.world_mut().resource_mut::<ConsoleRecorder>().start_recording(&path, 0).unwrap();
+        harness.run("gold set 5");
+        let request = json!({ "jsonrpc": "2.0", "id": 1, "method": "execute", "params": { "line": "gold set 6" } });
+        harness.world_mut().resource_scope(|world, toolbox: Mut<CLIToolBox>| toolbox.handle_rpc(&request, PermissionLevel::Admin, world));
+        harness.world_mut().resource_mut::<ConsoleRecorder>().stop_recording();
+
+        assert_eq!(recorded_lines(&path), vec!["gold set 5".to_string(), request.to_string()]);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn routed_commands_are_recorded_on_the_server() {
+        let path = recording_path("routed_recording");
+        let (mut client, mut server) = DevConsoleTestHarness::client_and_server();
+        for harness in [&mut client, &mut server] {
+            harness.insert_resource(Gold(0));
+            harness.toolbox_mut().direct_applyer::<u64, _>();
+            harness.add_reversible_command_at::<SetGold>("gold set").unwrap();
+        }
+
+        server.world_mut().resource_mut::<ConsoleRecorder>().start_recording(&path, 0).unwrap();
+        client.run("gold set 9");
+        server.advance(1);
+        server.world_mut().resource_mut::<ConsoleRecorder>().stop_recording();
+        assert_eq!(server.world().resource::<Gold>().0, 9);
+        assert_eq!(recorded_lines(&path).len(), 1);
+
+        server.world_mut().resource_mut::<Gold>().0 = 0;
+        let frame = current_frame(server.world());
+        server.world_mut().resource_mut::<ConsoleRecorder>().start_replay(&path, frame).unwrap();
+        server.advance(2);
+        assert_eq!(server.world().resource::<Gold>().0, 9);
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,425 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use bevy::prelude::*;
+use bevy::reflect::serde::{ReflectDeserializer, ReflectSerializer};
+use serde::de::DeserializeSeed;
+use serde_json::{json, Value};
+
+use crate::dev_api::*;
+use crate::events::{command_failed, with_source, CommandSource};
+use crate::frontend::DevConsoleSet;
+use crate::log_capture;
+use crate::permissions::builtin_permission;
+use crate::replay;
+use crate::rpc::output_to_json;
+use crate::CLIToolBox;
+
+/// Runs dev commands on the server of a multiplayer game when they change state the server owns.
+///
+/// Each [`DevCommand`] declares where it runs with [`DevCommand::runs_on`]. When the [`CommandRouting`]
+/// resource is inserted as a client, commands that run on the server are serialized with [`ReflectSerializer`]
+/// and sent through its [`CommandTransport`] instead of being applied, and commands that run on both are
+/// applied and sent. The server applies the commands it receives and sends their output back, which the
+/// client logs. Without the resource, every command runs where it is issued.
+///
+/// The server keeps the history of the commands it applies for each client, so undoing or redoing a reversible
+/// command sent by a client is sent to the server too, which undoes or redoes the most recent change of that client.
+pub struct CommandRoutingPlugin;
+
+impl Plugin for CommandRoutingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(PreUpdate, receive_routed_commands.in_set(DevConsoleSet::Dispatch));
+    }
+}
+
+/// Identifies the other end of a [`CommandTransport`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PeerId(pub u64);
+
+impl PeerId {
+    /// The server, the only peer of a client.
+    pub const SERVER: PeerId = PeerId(0);
+}
+
+/// Carries routed commands and their output between a client and a server, such as over the connection of the game.
+///
+/// Messages are JSON strings, delivered in order.
+pub trait CommandTransport: Send + Sync + 'static {
+    /// Send a message to a peer.
+    fn send(&mut self, peer: PeerId, message: String) -> std::io::Result<()>;
+
+    /// The messages received since the last call, with the peer that sent each.
+    fn receive(&mut self) -> Vec<(PeerId, String)>;
+}
+
+/// One end of an in-memory connection between a client and a server, for tests and for games running both in one process.
+// The demo app is never a client or a server, only the tests connect one
+#[cfg_attr(not(test), allow(dead_code))]
+pub struct LoopbackTransport {
+    /// The peer at the other end.
+    peer: PeerId,
+    incoming: Arc<Mutex<VecDeque<String>>>,
+    outgoing: Arc<Mutex<VecDeque<String>>>,
+}
+
+#[cfg_attr(not(test), allow(dead_code))]
+impl LoopbackTransport {
+    /// Create the client end and the server end of a connection.
+    pub fn pair() -> (LoopbackTransport, LoopbackTransport) {
+        Self::pair_for(PeerId(1))
+    }
+
+    /// Create the client end and the server end of a connection, the client being `peer` for the server.
+    pub fn pair_for(peer: PeerId) -> (LoopbackTransport, LoopbackTransport) {
+        let to_server = Arc::new(Mutex::new(VecDeque::new()));
+        let to_client = Arc::new(Mutex::new(VecDeque::new()));
+        let client = LoopbackTransport {
+            peer: PeerId::SERVER,
+            incoming: to_client.clone(),
+            outgoing: to_server.clone(),
+        };
+        let server = LoopbackTransport {
+            peer,
+            incoming: to_server,
+            outgoing: to_client,
+        };
+        (client, server)
+    }
+}
+
+impl CommandTransport for LoopbackTransport {
+    fn send(&mut self, peer: PeerId, message: String) -> std::io::Result<()> {
+        if peer != self.peer {
+            return Err(std::io::Error::new(std::io::ErrorKind::NotConnected, format!("no peer {}", peer.0)));
+        }
+        self.outgoing.lock().unwrap().push_back(message);
+        Ok(())
+    }
+
+    fn receive(&mut self) -> Vec<(PeerId, String)> {
+        self.incoming.lock().unwrap().drain(..).map(|message| (self.peer, message)).collect()
+    }
+}
+
+/// Which side of a multiplayer game this app is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(not(test), allow(dead_code))]
+pub enum NetworkRole {
+    Client,
+    Server,
+}
+
+/// Resource routing dev commands between a client and a server, see [`CommandRoutingPlugin`].
+#[derive(Resource)]
+pub struct CommandRouting {
+    pub role: NetworkRole,
+    /// The level the commands received from clients run at, on a server.
+    pub permission: PermissionLevel,
+    transport: Box<dyn CommandTransport>,
+}
+
+#[cfg_attr(not(test), allow(dead_code))]
+impl CommandRouting {
+    /// Send the commands that run on the server through `transport`.
+    pub fn client(transport: impl CommandTransport) -> Self {
+        CommandRouting {
+            role: NetworkRole::Client,
+            permission: PermissionLevel::Cheat,
+            transport: Box::new(transport),
+        }
+    }
+
+    /// Run the commands received through `transport` at the cheat level.
+    pub fn server(transport: impl CommandTransport) -> Self {
+        CommandRouting {
+            role: NetworkRole::Server,
+            permission: PermissionLevel::Cheat,
+            transport: Box::new(transport),
+        }
+    }
+}
+
+/// Where a command runs, as decided by [`CLIToolBox::route_dev_command`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandRoute {
+    /// The command runs here only.
+    Local,
+    /// The command was sent to the server, and does not run here.
+    Server,
+    /// The command was sent to the server, and also runs here.
+    Both,
+    /// The command runs nowhere, because it could not be sent to the server.
+    Failed,
+}
+
+impl CommandRoute {
+    /// Whether the command runs here.
+    pub fn runs_here(self) -> bool {
+        matches!(self, CommandRoute::Local | CommandRoute::Both)
+    }
+}
+
+impl CLIToolBox {
+    /// Send a parsed command to the server if it runs there, returning where it runs.
+    pub fn route_dev_command(&self, command: &dyn Reflect, metadata: &DevCommandMetadata, world: &mut World) -> CommandRoute {
+        let Some(mut routing) = world.get_resource_mut::<CommandRouting>() else {
+            return CommandRoute::Local;
+        };
+        if routing.role == NetworkRole::Server || metadata.runs_on == RunsOn::Client {
+            return CommandRoute::Local;
+        }
+
+        let value = match serde_json::to_value(ReflectSerializer::new(command, &self.type_registry)) {
+            Ok(value) => value,
+            Err(err) => {
+                error!("Failed to serialize {}: {}", metadata.path, err);
+                return CommandRoute::Failed;
+            },
+        };
+        let message = json!({ "command": metadata.qualified_path(), "value": value }).to_string();
+        let sent = match routing.transport.send(PeerId::SERVER, message) {
+            Ok(()) => {
+                info!("Sent {} to the server", metadata.path);
+                true
+            },
+            Err(err) => {
+                error!("Failed to send {} to the server: {}", metadata.path, err);
+                false
+            },
+        };
+        match (metadata.runs_on == RunsOn::Both, sent) {
+            (true, true) => CommandRoute::Both,
+            (true, false) => CommandRoute::Local,
+            (false, true) => CommandRoute::Server,
+            (false, false) => CommandRoute::Failed,
+        }
+    }
+
+    /// Ask the server to undo or redo its most recent change, returning whether the request was sent.
+    pub fn route_history(&self, undo: bool, world: &mut World) -> bool {
+        let action = if undo { "undo" } else { "redo" };
+        let Some(mut routing) = world.get_resource_mut::<CommandRouting>().filter(|routing| routing.role == NetworkRole::Client) else {
+            error!("Cannot {} a command of the server without a connection to it", action);
+            return false;
+        };
+        match routing.transport.send(PeerId::SERVER, json!({ "history": action }).to_string()) {
+            Ok(()) => true,
+            Err(err) => {
+                error!("Failed to send {} to the server: {}", action, err);
+                false
+            },
+        }
+    }
+
+    /// Apply a command a client sent, if it runs on the server and the client is allowed to run it,
+    /// or undo or redo the most recent change for the client.
+    pub(crate) fn run_routed_command(&self, message: &str, caller: PermissionLevel, world: &mut World) {
+        let message = serde_json::from_str::<Value>(message).unwrap_or_default();
+        if let Some(action) = message.get("history").and_then(Value::as_str) {
+            if let Err(err) = self.check_permission(action, builtin_permission(action), caller, world) {
+                error!("{}", err);
+                command_failed(world, action, err);
+                return;
+            }
+            match action {
+                "undo" => self.undo(caller, world),
+                "redo" => self.redo(caller, world),
+                _ => error!("Invalid routed command: {}", message),
+            }
+            return;
+        }
+        let (Some(qualified_path), Some(value)) = (message.get("command").and_then(Value::as_str), message.get("value")) else {
+            error!("Invalid routed command: {}", message);
+            return;
+        };
+        let Some(metadata) = self.metadatas.get(qualified_path) else {
+            error!("{}", self.unknown_command_error(qualified_path));
+            return;
+        };
+        if metadata.runs_on == RunsOn::Client {
+            error!("{} does not run on the server", metadata.path);
+            return;
+        }
+        if let Err(err) = self.check_permission(&metadata.path, metadata.permission, caller, world) {
+            error!("{}", err);
+            command_failed(world, &metadata.path, err);
+            return;
+        }
+
+        let command = match ReflectDeserializer::new(&self.type_registry).deserialize(value.clone()) {
+            Ok(command) => command,
+            Err(err) => {
+                error!("Failed to deserialize {}: {}", metadata.path, err);
+                return;
+            },
+        };
+        // A command of another type would not turn back into the type of this command
+        if command.get_represented_type_info().map(|info| info.type_id()) != Some(metadata.type_id) {
+            error!("Invalid value for {}", metadata.path);
+            return;
+        }
+        self.run_dev_command(command.as_ref(), metadata, world);
+    }
+}
+
+/// Apply the commands received by a server and reply with their output, or log the output received by a client.
+fn receive_routed_commands(world: &mut World) {
+    if !world.contains_resource::<CommandRouting>() {
+        return;
+    }
+    world.resource_scope(|world, mut routing: Mut<CommandRouting>| {
+        let messages = routing.transport.receive();
+        if messages.is_empty() {
+            return;
+        }
+
+        match routing.role {
+            NetworkRole::Server => world.resource_scope(|world, toolbox: Mut<CLIToolBox>| {
+                for (peer, message) in messages {
+                    let ((), output) = replay::recorded(world, &message, |world| {
+                        log_capture::capture_output(CommandSource::Peer(peer), || {
+                            with_source(world, CommandSource::Peer(peer), |world| toolbox.run_routed_command(&message, routing.permission, world))
+                        })
+                    });
+                    if let Err(err) = routing.transport.send(peer, output_to_json(output).to_string()) {
+                        error!("Failed to send the output of a command to peer {}: {}", peer.0, err);
+                    }
+                }
+            }),
+            NetworkRole::Client => {
+                for (_, message) in messages {
+                    let reply = serde_json::from_str::<Value>(&message).unwrap_or_default();
+                    let lines = |key: &str| reply.get(key).and_then(Value::as_array).cloned().unwrap_or_default();
+                    for line in lines("output") {
+                        info!("[server] {}", line.as_str().unwrap_or_default());
+                    }
+                    for line in lines("errors") {
+                        error!("[server] {}", line.as_str().unwrap_or_default());
+                    }
+                }
+            },
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::DevCommandFailed;
+    use crate::harness::DevConsoleTestHarness;
+    use crate::test_commands::{Gold, SetGold};
+
+    fn client_and_server() -> (DevConsoleTestHarness, DevConsoleTestHarness) {
+        let (mut client, mut server) = DevConsoleTestHarness::client_and_server();
+        for harness in [&mut client, &mut server] {
+            harness.insert_resource(Gold(1));
+            harness.toolbox_mut().direct_applyer::<u64, _>();
+            harness.add_reversible_command_at::<SetGold>("gold set").unwrap();
+        }
+        (client, server)
+    }
+
+    #[test]
+    fn commands_run_on_the_server() {
+        let (mut client, mut server) = client_and_server();
+        let output = client.run("gold set 9");
+        assert!(output.contains("Sent gold set to the server"), "{:?}", output);
+        assert_eq!(client.world().resource::<Gold>().0, 1);
+
+        server.advance(1);
+        assert_eq!(server.world().resource::<Gold>().0, 9);
+        assert_eq!(client.world().resource::<Gold>().0, 1);
+    }
+
+    #[test]
+    fn undo_and_redo_run_on_the_server() {
+        let (mut client, mut server) = client_and_server();
+        client.run("gold set 9");
+        server.advance(1);
+
+        assert!(client.run("undo").is_ok());
+        server.advance(1);
+        assert_eq!(server.world().resource::<Gold>().0, 1);
+
+        assert!(client.run("redo").is_ok());
+        server.advance(1);
+        assert_eq!(server.world().resource::<Gold>().0, 9);
+        assert_eq!(client.world().resource::<Gold>().0, 1);
+    }
+
+    /// The server ends of the connections to several clients.
+    struct Clients(Vec<LoopbackTransport>);
+
+    impl CommandTransport for Clients {
+        fn send(&mut self, peer: PeerId, message: String) -> std::io::Result<()> {
+            match self.0.iter_mut().find(|transport| transport.peer == peer) {
+                Some(transport) => transport.send(peer, message),
+                None => Err(std::io::Error::new(std::io::ErrorKind::NotConnected, format!("no peer {}", peer.0))),
+            }
+        }
+
+        fn receive(&mut self) -> Vec<(PeerId, String)> {
+            self.0.iter_mut().flat_map(|transport| transport.receive()).collect()
+        }
+    }
+
+    #[test]
+    fn clients_undo_their_own_changes() {
+        let (first_transport, first_server_end) = LoopbackTransport::pair_for(PeerId(1));
+        let (second_transport, second_server_end) = LoopbackTransport::pair_for(PeerId(2));
+        let (mut first, mut server) = client_and_server();
+        let (mut second, _) = client_and_server();
+        first.insert_resource(CommandRouting::client(first_transport));
+        second.insert_resource(CommandRouting::client(second_transport));
+        server.insert_resource(CommandRouting::server(Clients(vec![first_server_end, second_server_end])));
+
+        first.run("gold set 5");
+        server.advance(1);
+        second.run("gold set 9");
+        server.advance(1);
+        server.run("gold set 3");
+
+        // Undoing the change of the first client, and not the more recent ones of the second client or the server
+        first.run("undo");
+        server.advance(1);
+        assert_eq!(server.world().resource::<Gold>().0, 1);
+
+        second.run("undo");
+        server.advance(1);
+        assert_eq!(server.world().resource::<Gold>().0, 5);
+
+        first.run("undo");
+        server.advance(1);
+        assert_eq!(server.world().resource::<Gold>().0, 5);
+
+        assert!(server.run("undo").is_ok());
+        assert_eq!(server.world().resource::<Gold>().0, 9);
+    }
+
+    #[test]
+    fn failed_sends_are_reported() {
+        let (mut client, mut server) = client_and_server();
+        client.insert_resource(CommandRouting::client(Clients(Vec::new())));
+
+        let output = client.run("gold set 9");
+        assert!(output.has_error("Failed to send gold set"), "{:?}", output);
+        let events = client.world().resource::<Events<DevCommandFailed>>();
+        let errors = events.get_reader().read(events).map(|event| event.error.clone()).collect::<Vec<_>>();
+        assert!(matches!(errors.as_slice(), [DevToolParseError::NotSent(name)] if name == "gold set"), "{:?}", errors);
+        server.advance(1);
+        assert_eq!(server.world().resource::<Gold>().0, 1);
+    }
+
+    #[test]
+    fn undo_needs_a_connection() {
+        let (mut client, mut server) = client_and_server();
+        client.run("gold set 9");
+        server.advance(1);
+        client.world_mut().remove_resource::<CommandRouting>();
+
+        let output = client.run("undo");
+        assert!(output.has_error("without a connection"), "{:?}", output);
+        assert_eq!(server.world().resource::<Gold>().0, 9);
+    }
+}
@@ -0,0 +1,354 @@
+use bevy::prelude::*;
+use bevy::reflect::serde::TypedReflectDeserializer;
+use bevy::reflect::TypeInfo;
+use bevy::utils::{HashMap, HashSet};
+use serde::de::DeserializeSeed;
+use serde_json::{json, Value};
+
+use crate::dev_api::*;
+use crate::events::{command_failed, current_source, send_lifecycle_event, DevCommandParsed};
+use crate::log_capture::{self, LogLine};
+use crate::replay;
+use crate::{get_field_by_name, get_field_by_name_readonly, CLIToolBox};
+
+/// A JSON-RPC error code, message and data.
+type RpcError = (i64, String, Value);
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// A JSON-RPC 2.0 interface to the toolbox, for tools that call commands without scraping console output.
+///
+/// Methods:
+/// - `command` with `{"name": "gold set", "args": {"amount": 10}}` runs a command. Each argument is
+///   deserialized with the toolbox's `TypeRegistry` into the matching field of the default command.
+///   Destructive commands also need `"yes": true`.
+/// - `tool` with `{"name": "devflycamera", "args": {"movement_speed": 5.0}}` patches a modal dev tool.
+/// - `execute` with `{"line": "gold set 10"}` runs a line exactly as typed in a console.
+/// - `list` returns the name, description and arguments of every command and tool.
+/// - `schema` returns the JSON Schema document of [`CLIToolBox::schema`].
+///
+/// Successful calls return `{"output": [...], "errors": [...]}` with the lines logged while running.
+/// Commands and tools are only run when the caller's permission level allows them.
+/// `command`, `tool` and `execute` requests are added to the active recording, and replayed as requests.
+impl CLIToolBox {
+    /// Handle a JSON-RPC request given as text, returning the response as text.
+    pub fn handle_rpc_str(&self, request: &str, caller: PermissionLevel, world: &mut World) -> String {
+        let response = match serde_json::from_str::<Value>(request) {
+            Ok(request) => self.handle_rpc(&request, caller, world),
+            Err(err) => rpc_error(Value::Null, PARSE_ERROR, err.to_string(), Value::Null),
+        };
+        response.to_string()
+    }
+
+    /// Handle a JSON-RPC request from a caller at the given permission level.
+    pub fn handle_rpc(&self, request: &Value, caller: PermissionLevel, world: &mut World) -> Value {
+        let id = request.get("id").cloned().unwrap_or(Value::Null);
+        let Some(method) = request.get("method").and_then(Value::as_str) else {
+            return rpc_error(id, INVALID_REQUEST, "missing 'method'".to_string(), Value::Null);
+        };
+        let params = request.get("params").cloned().unwrap_or(Value::Null);
+
+        // Parse errors are returned in the response rather than logged
+        let source = current_source(world);
+        let run = |world: &mut World| log_capture::capture_output(source, || match method {
+            "command" => self.rpc_command(&params, caller, world),
+            "tool" => self.rpc_tool(&params, caller, world),
+            "execute" => match params.get("line").and_then(Value::as_str) {
+                Some(line) => {
+                    let source = current_source(world);
+                    Ok(output_to_json(self.execute_from(line, caller, source, world)))
+                },
+                None => Err((INVALID_PARAMS, "missing 'line'".to_string(), Value::Null)),
+            },
+            "list" => Ok(self.rpc_list()),
+            "schema" => Ok(self.schema()),
+            _ => Err((METHOD_NOT_FOUND, format!("unknown method '{}'", method), Value::Null)),
+        });
+        let (result, _) = match method {
+            "command" | "tool" | "execute" => replay::recorded(world, &request.to_string(), run),
+            _ => run(world),
+        };
+
+        match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message, data)) => rpc_error(id, code, message, data),
+        }
+    }
+
+    fn rpc_command(&self, params: &Value, caller: PermissionLevel, world: &mut World) -> Result<Value, RpcError> {
+        let (name, args) = name_and_args(params)?;
+        let words = name.split_whitespace().collect::<Vec<_>>();
+        let qualified_path = match self.find_command(&words) {
+            Ok(Some((qualified_path, len))) if len == words.len() => qualified_path,
+            Ok(_) => return Err(invalid_params(self.unknown_command_error(name))),
+            Err(err) => return Err(invalid_params(err)),
+        };
+        let metadata = &self.metadatas[&qualified_path];
+        self.check_permission(&metadata.path, metadata.permission, caller, world).map_err(invalid_params)?;
+
+        let mut command = self.create_dev_command(&qualified_path).map_err(invalid_params)?;
+        let mut provided = HashSet::new();
+        for (field_name, value) in args.iter() {
+            let field = get_field_by_name(command.as_mut(), field_name).map_err(invalid_params)?;
+            let value = self.deserialize_field(field, value).map_err(invalid_params)?;
+            field.apply(value.as_ref());
+            provided.insert(field_name.clone());
+        }
+        let metadata = self.finish_dev_command(&qualified_path, &provided).map_err(invalid_params)?;
+        // A remote caller cannot be asked, so it confirms up front
+        if metadata.destructive && params.get("yes").and_then(Value::as_bool) != Some(true) {
+            let err = DevToolParseError::NotConfirmed(metadata.path.clone());
+            command_failed(world, &metadata.path, err.clone());
+            return Err(invalid_params(err));
+        }
+        let source = current_source(world);
+        send_lifecycle_event(world, DevCommandParsed {
+            path: qualified_path,
+            command: command.clone_value(),
+            source,
+        });
+
+        let ((), output) = log_capture::capture_output(source, || self.run_dev_command(command.as_ref(), &metadata, world));
+        Ok(output_to_json(output))
+    }
+
+    fn rpc_tool(&self, params: &Value, caller: PermissionLevel, world: &mut World) -> Result<Value, RpcError> {
+        let (name, args) = name_and_args(params)?;
+        let qualified_name = match self.find_tool(name) {
+            Ok(Some(qualified_name)) => qualified_name,
+            Ok(None) => return Err(invalid_params(self.unknown_command_error(name))),
+            Err(err) => return Err(invalid_params(err)),
+        };
+        let permission = self.tool_metadatas[&qualified_name].permission;
+        self.check_permission(name, permission, caller, world).map_err(invalid_params)?;
+
+        let mut patch = HashMap::new();
+        {
+            let tool = (self.get_tool_fn[&qualified_name])(world);
+            for (field_name, value) in args.iter() {
+                let field = get_field_by_name_readonly(tool, field_name).map_err(invalid_params)?;
+                let value = self.deserialize_field(field, value).map_err(invalid_params)?;
+                patch.insert(field_name.clone(), value);
+            }
+        }
+
+        let source = current_source(world);
+        let (result, output) = log_capture::capture_output(source, || self.patch_tool(&qualified_name, patch, world));
+        result.map_err(invalid_params)?;
+        Ok(output_to_json(output))
+    }
+
+    fn rpc_list(&self) -> Value {
+        let mut commands = self.metadatas
+            .iter()
+            .map(|(qualified_path, metadata)| json!({
+                "name": metadata.path,
+                "qualified_name": qualified_path,
+                "description": metadata.short_description,
+                "usage": metadata.usage(),
+                "permission": metadata.permission.to_string(),
+                "runs_on": metadata.runs_on.to_string(),
+                "destructive": metadata.destructive,
+                "arguments": field_types(metadata.type_info)
+                    .into_iter()
+                    .map(|(name, type_path)| {
+                        let requirement = match metadata.requirement(&name) {
+                            ArgRequirement::Required => json!("required"),
+                            ArgRequirement::Optional => json!("optional"),
+                            ArgRequirement::Default(value) => json!({ "default": value }),
+                        };
+                        json!({ "name": name, "type": type_path, "requirement": requirement })
+                    })
+                    .collect::<Vec<_>>(),
+            }))
+            .collect::<Vec<_>>();
+        commands.sort_by(|a, b| a["qualified_name"].as_str().cmp(&b["qualified_name"].as_str()));
+
+        let mut tools = self.tool_metadatas
+            .iter()
+            .map(|(qualified_name, metadata)| json!({
+                "name": metadata.name.to_lowercase(),
+                "qualified_name": qualified_name,
+                "description": metadata.short_description,
+                "permission": metadata.permission.to_string(),
+                "fields": field_types(metadata.type_info)
+                    .into_iter()
+                    .map(|(name, type_path)| json!({ "name": name, "type": type_path }))
+                    .collect::<Vec<_>>(),
+            }))
+            .collect::<Vec<_>>();
+        tools.sort_by(|a, b| a["qualified_name"].as_str().cmp(&b["qualified_name"].as_str()));
+
+        json!({ "commands": commands, "tools": tools })
+    }
+
+    /// Deserialize a JSON value into a new value of the same type as `field`.
+    pub fn deserialize_field(&self, field: &dyn Reflect, value: &Value) -> Result<Box<dyn Reflect>, DevToolParseError> {
+        let Some(registration) = field
+            .get_represented_type_info()
+            .and_then(|type_info| self.type_registry.get(type_info.type_id()))
+        else {
+            return Err(DevToolParseError::InvalidJson(format!("type {} is not registered", field.reflect_type_path())));
+        };
+
+        TypedReflectDeserializer::new(registration, &self.type_registry)
+            .deserialize(value)
+            .map_err(|err| DevToolParseError::InvalidJson(err.to_string()))
+    }
+}
+
+/// The names and type paths of the fields described by `type_info`.
+fn field_types(type_info: &TypeInfo) -> Vec<(String, &'static str)> {
+    match type_info {
+        TypeInfo::Struct(info) => info.iter().map(|field| (field.name().to_string(), field.type_path())).collect(),
+        TypeInfo::TupleStruct(info) => info.iter().map(|field| (field.index().to_string(), field.type_path())).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn name_and_args(params: &Value) -> Result<(&str, serde_json::Map<String, Value>), RpcError> {
+    let Some(name) = params.get("name").and_then(Value::as_str) else {
+        return Err((INVALID_PARAMS, "missing 'name'".to_string(), Value::Null));
+    };
+    let args = match params.get("args") {
+        None | Some(Value::Null) => serde_json::Map::new(),
+        Some(Value::Object(args)) => args.clone(),
+        Some(_) => return Err((INVALID_PARAMS, "'args' must be an object".to_string(), Value::Null)),
+    };
+    Ok((name, args))
+}
+
+pub fn output_to_json(output: Vec<LogLine>) -> Value {
+    let (errors, output): (Vec<_>, Vec<_>) = output.into_iter().partition(|line| line.level <= bevy::log::Level::WARN);
+    json!({
+        "output": output.into_iter().map(|line| line.message).collect::<Vec<_>>(),
+        "errors": errors.into_iter().map(|line| line.message).collect::<Vec<_>>(),
+    })
+}
+
+fn invalid_params(err: DevToolParseError) -> RpcError {
+    (INVALID_PARAMS, err.to_string(), error_data(&err))
+}
+
+/// Structured details of a parse error.
+pub fn error_data(err: &DevToolParseError) -> Value {
+    match err {
+        DevToolParseError::MissingArguments(missing) => json!({ "missing": missing }),
+        DevToolParseError::AmbiguousName(candidates) => json!({ "candidates": candidates }),
+        DevToolParseError::PermissionDenied { name, required } => json!({ "name": name, "required": required.to_string() }),
+        DevToolParseError::CheatsDisabled(name) => json!({ "name": name, "required": "cheat" }),
+        DevToolParseError::NotConfirmed(name) => json!({ "name": name, "destructive": true }),
+        DevToolParseError::Vetoed { name, reason } => json!({ "name": name, "reason": reason }),
+        DevToolParseError::UnknownCommand { name, suggestions } | DevToolParseError::UnknownField { name, suggestions } => {
+            json!({ "name": name, "suggestions": suggestions })
+        },
+        DevToolParseError::UnknownPreset { tool, name, suggestions } => json!({ "tool": tool, "name": name, "suggestions": suggestions }),
+        _ => Value::Null,
+    }
+}
+
+fn rpc_error(id: Value, code: i64, message: String, data: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message, "data": data } })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::events::{with_source, CommandSource, DevCommandFailed};
+    use crate::harness::DevConsoleTestHarness;
+    use crate::replay::ConsoleRecorder;
+    use crate::test_commands::{Gold, SetGold};
+
+    /// Sets the player's gold to zero.
+    #[derive(Reflect, Debug, Default)]
+    struct ClearGold;
+
+    impl bevy::ecs::world::Command for ClearGold {
+        fn apply(self, world: &mut World) {
+            world.resource_mut::<Gold>().0 = 0;
+        }
+    }
+
+    impl FromStr for ClearGold {
+        type Err = DevToolParseError;
+        fn from_str(_s: &str) -> Result<Self, DevToolParseError> {
+            Ok(ClearGold)
+        }
+    }
+
+    impl DevCommand for ClearGold {
+        fn short_description() -> Option<&'static str> {
+            None
+        }
+
+        fn destructive() -> bool {
+            true
+        }
+    }
+
+    fn harness() -> DevConsoleTestHarness {
+        let mut harness = DevConsoleTestHarness::new();
+        harness.insert_resource(Gold(10));
+        harness.add_command_at::<SetGold>("gold set").unwrap();
+        harness.add_command_at::<ClearGold>("gold clear").unwrap();
+        harness
+    }
+
+    /// Handle a request from remote connection 7.
+    fn rpc(harness: &mut DevConsoleTestHarness, request: Value) -> Value {
+        harness.world_mut().resource_scope(|world, toolbox: Mut<CLIToolBox>| {
+            with_source(world, CommandSource::Remote(7), |world| toolbox.handle_rpc(&request, PermissionLevel::Admin, world))
+        })
+    }
+
+    #[test]
+    fn execute_keeps_the_remote_source() {
+        let mut harness = harness();
+        let response = rpc(&mut harness, json!({ "jsonrpc": "2.0", "id": 1, "method": "execute", "params": { "line": "gold set" } }));
+        assert!(response["result"]["errors"][0].as_str().unwrap().contains("amount"));
+
+        let events = harness.world().resource::<Events<DevCommandFailed>>();
+        let sources = events.get_reader().read(events).map(|event| event.source).collect::<Vec<_>>();
+        assert_eq!(sources, vec![CommandSource::Remote(7)]);
+    }
+
+    #[test]
+    fn destructive_commands_need_yes() {
+        let mut harness = harness();
+        let response = rpc(&mut harness, json!({ "jsonrpc": "2.0", "id": 1, "method": "command", "params": { "name": "gold clear" } }));
+        assert_eq!(response["error"]["code"], json!(INVALID_PARAMS));
+        assert_eq!(response["error"]["data"], json!({ "name": "gold clear", "destructive": true }));
+        assert_eq!(harness.world().resource::<Gold>().0, 10);
+
+        let response = rpc(&mut harness, json!({ "jsonrpc": "2.0", "id": 2, "method": "execute", "params": { "line": "gold clear" } }));
+        assert!(response["result"]["errors"][0].as_str().unwrap().contains("not confirmed"));
+        assert_eq!(harness.world().resource::<Gold>().0, 10);
+
+        let response = rpc(&mut harness, json!({ "jsonrpc": "2.0", "id": 3, "method": "command", "params": { "name": "gold clear", "yes": true } }));
+        assert!(response.get("error").is_none());
+        assert_eq!(harness.world().resource::<Gold>().0, 0);
+    }
+
+    #[test]
+    fn commands_are_recorded_and_replayed() {
+        let path = std::env::temp_dir().join(format!("rpc_recording_{}.txt", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut harness = harness();
+        harness.world_mut().resource_mut::<ConsoleRecorder>().start_recording(path, 0).unwrap();
+        let request = json!({ "jsonrpc": "2.0", "id": 1, "method": "command", "params": { "name": "gold set", "args": { "amount": 42 } } });
+        rpc(&mut harness, request.clone());
+        harness.world_mut().resource_mut::<ConsoleRecorder>().stop_recording();
+        assert!(std::fs::read_to_string(path).unwrap().contains(&request.to_string()));
+
+        harness.world_mut().resource_mut::<Gold>().0 = 0;
+        harness.world_mut().resource_mut::<ConsoleRecorder>().start_replay(path, 0).unwrap();
+        harness.advance(1);
+        assert_eq!(harness.world().resource::<Gold>().0, 42);
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,237 @@
+use std::any::TypeId;
+
+use bevy::prelude::*;
+use bevy::reflect::serde::TypedReflectSerializer;
+use bevy::reflect::{TypeInfo, VariantInfo};
+use serde_json::{json, Map, Value};
+
+use crate::dev_api::*;
+use crate::CLIToolBox;
+
+/// Export a JSON Schema of every registered command and tool, so that external UIs and
+/// editor autocompletion can be generated from it.
+///
+/// The document has a `commands` and a `tools` object, keyed by qualified name, whose values are
+/// JSON Schemas of the arguments. Each schema carries the type's documentation as its `description`,
+/// the `Default` value of each field as its `default`, and the extra `x-name`, `x-usage`,
+/// `x-permission`, `x-runs-on`, `x-destructive` and `x-cli-default` keys used by consoles.
+impl CLIToolBox {
+    pub fn schema(&self) -> Value {
+        let mut commands = Map::new();
+        for (qualified_path, metadata) in self.metadatas.iter() {
+            let default = (metadata.create_default_fn)();
+            let mut schema = self.object_schema(metadata.type_info, Some(default.as_ref()));
+            schema.insert("title".to_string(), json!(metadata.path));
+            schema.insert("x-name".to_string(), json!(metadata.path));
+            schema.insert("x-usage".to_string(), json!(metadata.usage()));
+            schema.insert("x-permission".to_string(), json!(metadata.permission.to_string()));
+            schema.insert("x-runs-on".to_string(), json!(metadata.runs_on.to_string()));
+            schema.insert("x-destructive".to_string(), json!(metadata.destructive));
+            if let Some(description) = metadata.short_description {
+                schema.entry("description").or_insert(json!(description));
+            }
+
+            let mut required = Vec::new();
+            for (name, requirement) in metadata.arguments.iter() {
+                match requirement {
+                    ArgRequirement::Required => required.push(json!(name)),
+                    ArgRequirement::Default(value) => {
+                        if let Some(property) = schema.get_mut("properties").and_then(|properties| properties.get_mut(*name)) {
+                            property["x-cli-default"] = json!(value);
+                        }
+                    },
+                    ArgRequirement::Optional => {},
+                }
+            }
+            schema.insert("required".to_string(), Value::Array(required));
+
+            commands.insert(qualified_path.clone(), Value::Object(schema));
+        }
+
+        let mut tools = Map::new();
+        for (qualified_name, metadata) in self.tool_metadatas.iter() {
+            let default = (metadata.create_default_fn)();
+            let mut schema = self.object_schema(metadata.type_info, Some(default.as_ref()));
+            schema.insert("title".to_string(), json!(metadata.name.to_lowercase()));
+            schema.insert("x-name".to_string(), json!(metadata.name.to_lowercase()));
+            schema.insert("x-permission".to_string(), json!(metadata.permission.to_string()));
+            if let Some(description) = metadata.short_description {
+                schema.entry("description").or_insert(json!(description));
+            }
+
+            tools.insert(qualified_name.clone(), Value::Object(schema));
+        }
+
+        json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "commands": commands,
+            "tools": tools,
+        })
+    }
+
+    /// The schema of a command or tool type, with the values of `default` as field defaults.
+    fn object_schema(&self, type_info: &TypeInfo, default: Option<&dyn Reflect>) -> Map<String, Value> {
+        let mut schema = match self.type_schema(type_info) {
+            Value::Object(schema) => schema,
+            _ => Map::new(),
+        };
+
+        if let (Some(default), Some(Value::Object(properties))) = (default, schema.get_mut("properties")) {
+            if let bevy::reflect::ReflectRef::Struct(default) = default.reflect_ref() {
+                for (idx, field) in default.iter_fields().enumerate() {
+                    let (Some(name), Ok(value)) = (default.name_at(idx), serde_json::to_value(TypedReflectSerializer::new(field, &self.type_registry))) else {
+                        continue;
+                    };
+                    if let Some(Value::Object(property)) = properties.get_mut(name) {
+                        property.insert("default".to_string(), value);
+                    }
+                }
+            }
+        }
+
+        schema
+    }
+
+    /// The schema of the type with the given id, or a schema naming its type path if it is not registered.
+    fn type_id_schema(&self, type_id: TypeId, type_path: &str) -> Value {
+        match self.type_registry.get_type_info(type_id) {
+            Some(type_info) => self.type_schema(type_info),
+            None => json!({ "x-type-path": type_path }),
+        }
+    }
+
+    fn type_schema(&self, type_info: &TypeInfo) -> Value {
+        let type_path = type_info.type_path();
+        let mut schema = match type_info {
+            TypeInfo::Struct(info) => {
+                let properties = info
+                    .iter()
+                    .map(|field| {
+                        let mut property = self.type_id_schema(field.type_id(), field.type_path());
+                        if let (Some(docs), Value::Object(property)) = (field.docs(), &mut property) {
+                            property.insert("description".to_string(), json!(docs.trim()));
+                        }
+                        (field.name().to_string(), property)
+                    })
+                    .collect::<Map<_, _>>();
+                json!({ "type": "object", "properties": properties, "additionalProperties": false })
+            },
+            TypeInfo::TupleStruct(info) => {
+                let items = info.iter().map(|field| self.type_id_schema(field.type_id(), field.type_path())).collect::<Vec<_>>();
+                json!({ "type": "array", "prefixItems": items, "items": false })
+            },
+            TypeInfo::Tuple(info) => {
+                let items = info.iter().map(|field| self.type_id_schema(field.type_id(), field.type_path())).collect::<Vec<_>>();
+                json!({ "type": "array", "prefixItems": items, "items": false })
+            },
+            TypeInfo::List(info) => {
+                json!({ "type": "array", "items": self.type_id_schema(info.item_type_id(), info.item_type_path_table().path()) })
+            },
+            TypeInfo::Array(info) => json!({
+                "type": "array",
+                "items": self.type_id_schema(info.item_type_id(), info.item_type_path_table().path()),
+                "minItems": info.capacity(),
+                "maxItems": info.capacity(),
+            }),
+            TypeInfo::Map(info) => {
+                json!({ "type": "object", "additionalProperties": self.type_id_schema(info.value_type_id(), info.value_type_path_table().path()) })
+            },
+            TypeInfo::Enum(info) => {
+                if type_path.starts_with("core::option::Option<") {
+                    // Options are (de)serialized as their value or null
+                    let some = info.variant("Some").and_then(|variant| match variant {
+                        VariantInfo::Tuple(variant) => variant.field_at(0),
+                        _ => None,
+                    });
+                    let some = some.map_or(json!({}), |field| self.type_id_schema(field.type_id(), field.type_path()));
+                    json!({ "anyOf": [some, { "type": "null" }] })
+                } else if info.iter().all(|variant| matches!(variant, VariantInfo::Unit(_))) {
+                    json!({ "type": "string", "enum": info.variant_names() })
+                } else {
+                    let variants = info.iter().map(|variant| self.variant_schema(variant)).collect::<Vec<_>>();
+                    json!({ "oneOf": variants })
+                }
+            },
+            TypeInfo::Value(_) => value_schema(type_path),
+        };
+
+        if let Value::Object(schema) = &mut schema {
+            schema.insert("x-type-path".to_string(), json!(type_path));
+            if let Some(docs) = type_info.docs() {
+                schema.insert("description".to_string(), json!(docs.trim()));
+            }
+        }
+        schema
+    }
+
+    fn variant_schema(&self, variant: &VariantInfo) -> Value {
+        let value = match variant {
+            VariantInfo::Unit(variant) => return json!({ "const": variant.name() }),
+            VariantInfo::Struct(variant) => {
+                let properties = variant
+                    .iter()
+                    .map(|field| (field.name().to_string(), self.type_id_schema(field.type_id(), field.type_path())))
+                    .collect::<Map<_, _>>();
+                json!({ "type": "object", "properties": properties })
+            },
+            VariantInfo::Tuple(variant) => {
+                let items = variant.iter().map(|field| self.type_id_schema(field.type_id(), field.type_path())).collect::<Vec<_>>();
+                json!({ "type": "array", "prefixItems": items, "items": false })
+            },
+        };
+        json!({
+            "type": "object",
+            "properties": { variant.name(): value },
+            "required": [variant.name()],
+            "additionalProperties": false,
+        })
+    }
+}
+
+/// The schema of an opaque value type, from its type path.
+fn value_schema(type_path: &str) -> Value {
+    match type_path {
+        "bool" => json!({ "type": "boolean" }),
+        "u8" | "u16" | "u32" | "u64" | "u128" | "usize" => json!({ "type": "integer", "minimum": 0 }),
+        "i8" | "i16" | "i32" | "i64" | "i128" | "isize" => json!({ "type": "integer" }),
+        "f32" | "f64" => json!({ "type": "number" }),
+        "char" => json!({ "type": "string", "minLength": 1, "maxLength": 1 }),
+        "alloc::string::String" | "str" | "alloc::borrow::Cow<str>" => json!({ "type": "string" }),
+        _ => json!({}),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::harness::DevConsoleTestHarness;
+    use crate::test_commands::SetGold;
+    use crate::test_tool::DevFlyCamera;
+
+    #[test]
+    fn commands_and_tools_are_described() {
+        let mut harness = DevConsoleTestHarness::new();
+        harness.insert_resource(DevFlyCamera::default());
+        harness.add_command_at::<SetGold>("gold set").unwrap();
+        harness.add_tool::<DevFlyCamera>().unwrap();
+        let schema = harness.toolbox().schema();
+
+        let (gold_set, _) = harness.toolbox().find_command(&["gold", "set"]).unwrap().unwrap();
+        let command = &schema["commands"][gold_set];
+        assert_eq!(command["title"], json!("gold set"));
+        assert_eq!(command["required"], json!(["amount"]));
+        assert_eq!(command["properties"]["amount"]["type"], json!("integer"));
+        assert_eq!(command["x-usage"], json!("gold set <amount>"));
+        assert_eq!(command["x-permission"], json!("cheat"));
+
+        // Doc comments are reflected with the `documentation` feature of bevy_reflect
+        let tool = &schema["tools"][DevFlyCamera::metadata().qualified_name()];
+        assert!(tool["description"].as_str().unwrap().starts_with("A flying camera controller"), "{}", tool);
+        assert_eq!(tool["properties"]["enabled"]["type"], json!("boolean"));
+        assert_eq!(tool["properties"]["enabled"]["default"], json!(false));
+        let movement_speed = &tool["properties"]["movement_speed"];
+        assert_eq!(movement_speed["anyOf"][1], json!({ "type": "null" }));
+        assert_eq!(movement_speed["default"], json!(3.0));
+        assert_eq!(movement_speed["description"], json!("How fast the camera travels forwards, backwards, left, right, up and down, in world units."));
+    }
+}
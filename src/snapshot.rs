@@ -0,0 +1,251 @@
+use bevy::ecs::entity::EntityHashMap;
+use bevy::ecs::reflect::{AppTypeRegistry, ReflectComponent, ReflectResource};
+use bevy::prelude::*;
+use bevy::scene::serde::SceneDeserializer;
+use bevy::scene::{ron, DynamicScene, DynamicSceneBuilder, SceneFilter};
+use bevy::utils::HashMap;
+use serde::de::DeserializeSeed;
+
+use crate::CLIToolBox;
+
+/// Snapshots of the reflectable resources and entities of the world, saved and restored with the `snapshot` builtin.
+///
+/// - `snapshot save <name>` stores a snapshot in memory, or on disk as RON when the name ends with `.ron`.
+/// - `snapshot load <name>` restores it: entities that still exist get the saved values of their components,
+///   and despawned entities are spawned again. Entities spawned after the snapshot are left alone.
+///   The entities of a snapshot file are always spawned as new entities, as the ones with the same ids
+///   in this run are unrelated to them. As it overwrites the world, it asks for confirmation unless `--yes` is given.
+/// - `snapshot list` lists the snapshots kept in memory.
+///
+/// `save` takes `--only <types>` to only include the given comma separated components and resources,
+/// and `--exclude <types>` to leave them out. Types are named by their short or full type path.
+#[derive(Resource)]
+pub struct WorldSnapshots {
+    /// Which components are included in snapshots.
+    pub components: SceneFilter,
+    /// Which resources are included in snapshots. By default, every resource but the clocks.
+    pub resources: SceneFilter,
+    snapshots: HashMap<String, DynamicScene>,
+}
+
+impl Default for WorldSnapshots {
+    fn default() -> Self {
+        WorldSnapshots {
+            components: SceneFilter::default(),
+            // Restoring the clocks would move time backwards for every system
+            resources: SceneFilter::default()
+                .deny::<Time>()
+                .deny::<Time<Real>>()
+                .deny::<Time<Virtual>>()
+                .deny::<Time<Fixed>>(),
+            snapshots: HashMap::new(),
+        }
+    }
+}
+
+impl CLIToolBox {
+    /// Handle the `snapshot` builtin.
+    pub fn snapshot_command(&self, args: &[&str], world: &mut World) {
+        if !world.contains_resource::<WorldSnapshots>() {
+            world.init_resource::<WorldSnapshots>();
+        }
+        world.resource_scope(|world, mut snapshots: Mut<WorldSnapshots>| match args {
+            ["save", name, filters @ ..] => {
+                let Some((components, resources)) = snapshot_filters(&snapshots, filters, world) else {
+                    return;
+                };
+                let scene = take_snapshot(world, components, resources);
+                let (entities, resources) = (scene.entities.len(), scene.resources.len());
+                if is_file(name) {
+                    let registry = world.resource::<AppTypeRegistry>().read();
+                    let result = scene.serialize(&registry).map_err(|err| err.to_string())
+                        .and_then(|ron| std::fs::write(name, ron).map_err(|err| err.to_string()));
+                    if let Err(err) = result {
+                        error!("Failed to save snapshot to {}: {}", name, err);
+                        return;
+                    }
+                } else {
+                    snapshots.snapshots.insert(name.to_string(), scene);
+                }
+                info!("Saved snapshot {} with {} entities and {} resources", name, entities, resources);
+            },
+            ["load", name] => {
+                let result = if is_file(name) {
+                    read_snapshot(name, world).and_then(|scene| restore_snapshot(&scene, false, world))
+                } else {
+                    match snapshots.snapshots.get(*name) {
+                        Some(scene) => restore_snapshot(scene, true, world),
+                        None => Err(format!("no snapshot named {}", name)),
+                    }
+                };
+                match result {
+                    Ok(()) => info!("Loaded snapshot {}", name),
+                    Err(err) => error!("Failed to load snapshot {}: {}", name, err),
+                }
+            },
+            ["list"] => {
+                if snapshots.snapshots.is_empty() {
+                    info!("No snapshots");
+                    return;
+                }
+                let mut names = snapshots.snapshots.keys().collect::<Vec<_>>();
+                names.sort();
+                for name in names {
+                    let scene = &snapshots.snapshots[name];
+                    info!("  {} - {} entities, {} resources", name, scene.entities.len(), scene.resources.len());
+                }
+            },
+            _ => error!("Usage: snapshot save <name> [--only <types>] [--exclude <types>] | snapshot load <name> | snapshot list"),
+        });
+    }
+}
+
+/// Whether a snapshot name refers to a file rather than a snapshot kept in memory.
+fn is_file(name: &str) -> bool {
+    name.ends_with(".ron")
+}
+
+/// The component and resource filters for a snapshot, from the settings and the `--only` and `--exclude` arguments.
+fn snapshot_filters(snapshots: &WorldSnapshots, args: &[&str], world: &World) -> Option<(SceneFilter, SceneFilter)> {
+    let mut components = snapshots.components.clone();
+    let mut resources = snapshots.resources.clone();
+    let registry = world.resource::<AppTypeRegistry>().read();
+
+    // Whether the filters were already replaced by the types given to `--only`
+    let mut restricted = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let only = match *arg {
+            "--only" => true,
+            "--exclude" => false,
+            _ => {
+                error!("Unexpected snapshot argument: {}", arg);
+                return None;
+            },
+        };
+        let Some(types) = args.next() else {
+            error!("Missing types after {}", arg);
+            return None;
+        };
+        if only && !restricted {
+            components = SceneFilter::deny_all();
+            resources = SceneFilter::deny_all();
+            restricted = true;
+        }
+
+        for name in types.split(',').filter(|name| !name.is_empty()) {
+            let Some(registration) = registry.get_with_short_type_path(name).or_else(|| registry.get_with_type_path(name)) else {
+                error!("Unknown or ambiguous type: {}", name);
+                return None;
+            };
+            let type_id = registration.type_id();
+            let filter = if registration.data::<ReflectComponent>().is_some() {
+                &mut components
+            } else if registration.data::<ReflectResource>().is_some() {
+                &mut resources
+            } else {
+                error!("{} is neither a reflected component nor a reflected resource", name);
+                return None;
+            };
+            let previous = std::mem::take(filter);
+            *filter = if only { previous.allow_by_id(type_id) } else { previous.deny_by_id(type_id) };
+        }
+    }
+    Some((components, resources))
+}
+
+fn take_snapshot(world: &World, components: SceneFilter, resources: SceneFilter) -> DynamicScene {
+    DynamicSceneBuilder::from_world(world)
+        .with_filter(components)
+        .with_resource_filter(resources)
+        .extract_entities(world.iter_entities().map(|entity| entity.id()))
+        .extract_resources()
+        .build()
+}
+
+fn read_snapshot(path: &str, world: &World) -> Result<DynamicScene, String> {
+    let contents = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+    let mut deserializer = ron::de::Deserializer::from_str(&contents).map_err(|err| err.to_string())?;
+    let registry = world.resource::<AppTypeRegistry>().read();
+    SceneDeserializer { type_registry: &registry }
+        .deserialize(&mut deserializer)
+        .map_err(|err| err.to_string())
+}
+
+/// Write a snapshot back to the world.
+///
+/// A snapshot taken during this run is written back to the entities it was taken from, spawning the ones that were
+/// despawned since. Other snapshots are spawned as new entities.
+fn restore_snapshot(scene: &DynamicScene, same_run: bool, world: &mut World) -> Result<(), String> {
+    let mut entity_map = if same_run {
+        scene.entities
+            .iter()
+            .filter(|entity| world.get_entity(entity.entity).is_some())
+            .map(|entity| (entity.entity, entity.entity))
+            .collect::<EntityHashMap<_>>()
+    } else {
+        EntityHashMap::default()
+    };
+    scene.write_to_world(world, &mut entity_map).map_err(|err| err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::harness::DevConsoleTestHarness;
+
+    #[derive(Component, Reflect, Default)]
+    #[reflect(Component)]
+    struct Health(u32);
+
+    fn harness() -> DevConsoleTestHarness {
+        let mut harness = DevConsoleTestHarness::new();
+        harness.app.register_type::<Health>();
+        harness
+    }
+
+    fn healths(harness: &mut DevConsoleTestHarness) -> Vec<u32> {
+        let mut healths = harness.world_mut().query::<&Health>().iter(harness.world()).map(|health| health.0).collect::<Vec<_>>();
+        healths.sort();
+        healths
+    }
+
+    #[test]
+    fn snapshots_are_saved_listed_and_loaded() {
+        let mut harness = harness();
+        let kept = harness.world_mut().spawn(Health(10)).id();
+        let despawned = harness.world_mut().spawn(Health(20)).id();
+        assert!(harness.run("snapshot save start").contains("Saved snapshot start with 2 entities"));
+        assert!(harness.run("snapshot list").contains("start - 2 entities"));
+
+        harness.world_mut().entity_mut(kept).insert(Health(1));
+        harness.world_mut().despawn(despawned);
+        harness.world_mut().spawn(Health(30));
+        let output = harness.run("snapshot load start --yes");
+        assert!(output.contains("Loaded snapshot start"), "{:?}", output);
+
+        assert_eq!(harness.world().get::<Health>(kept).unwrap().0, 10);
+        assert_eq!(healths(&mut harness), vec![10, 20, 30]);
+    }
+
+    #[test]
+    fn snapshot_files_spawn_new_entities() {
+        let path = std::env::temp_dir().join(format!("snapshot_{}.ron", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        let mut saved = harness();
+        saved.world_mut().spawn(Health(10));
+        let output = saved.run(&format!("snapshot save {}", path));
+        assert!(output.contains("with 1 entities"), "{:?}", output);
+
+        // The entity of this world has the id the saved entity had, but is another entity
+        let mut harness = harness();
+        let unrelated = harness.world_mut().spawn(Health(99)).id();
+        let output = harness.run(&format!("snapshot load {} --yes", path));
+        std::fs::remove_file(path).unwrap();
+        assert!(output.is_ok(), "{:?}", output);
+
+        assert_eq!(harness.world().get::<Health>(unrelated).unwrap().0, 99);
+        assert_eq!(healths(&mut harness), vec![10, 99]);
+    }
+}
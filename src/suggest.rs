@@ -0,0 +1,41 @@
+/// The Levenshtein edit distance between two strings, counted in characters.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    // Distances from the current prefix of `a` to every prefix of `b`
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(a_char != *b_char);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+
+    row[b.len()]
+}
+
+/// The candidates that are close enough to `name` to be what the user meant, closest first.
+///
+/// A candidate is close enough when it is within one edit for every three characters of `name`
+/// (and at least one edit). At most three suggestions are returned.
+pub fn suggest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let name = name.to_lowercase();
+    let max_distance = (name.chars().count() / 3).max(1);
+
+    let mut suggestions = candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(&name, &candidate.to_lowercase()), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect::<Vec<_>>();
+    suggestions.sort();
+    suggestions.dedup_by(|(_, a), (_, b)| a == b);
+
+    suggestions
+        .into_iter()
+        .take(3)
+        .map(|(_, candidate)| candidate.to_string())
+        .collect()
+}
@@ -0,0 +1,88 @@
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::{Arc, Mutex};
+
+use rustyline::error::ReadlineError;
+use rustyline::history::DefaultHistory;
+use rustyline::ExternalPrinter;
+
+use crate::completion::{CompletionTree, ConsoleHelper};
+use crate::frontend::DevConsoleFrontend;
+use crate::log_capture::LogOutput;
+
+/// A console frontend reading lines from the terminal with rustyline.
+///
+/// The editor runs on its own thread so that waiting for input never blocks the app.
+/// `CTRL-C` submits `cancel`, which cancels the most recent job, and `CTRL-D` submits `exit`.
+/// Log records written to the [`LogOutput`] are printed above the prompt, which is redrawn after them.
+pub struct RustylineFrontend {
+    input: Mutex<Receiver<String>>,
+    printer: Mutex<Box<dyn ExternalPrinter + Send>>,
+    completions: Arc<Mutex<CompletionTree>>,
+}
+
+impl RustylineFrontend {
+    /// Start reading the terminal on a new thread, and take the log records of `log_output` when given.
+    pub fn spawn(log_output: Option<&LogOutput>) -> rustyline::Result<Self> {
+        let completions = Arc::new(Mutex::new(CompletionTree::default()));
+        let mut editor = rustyline::Editor::<ConsoleHelper, DefaultHistory>::new()?;
+        editor.set_helper(Some(ConsoleHelper(completions.clone())));
+        let printer = editor.create_external_printer()?;
+        if let Some(log_output) = log_output {
+            let mut log_printer = editor.create_external_printer()?;
+            log_output.redirect(move |record| {
+                let _ = log_printer.print(record);
+            });
+        }
+
+        let (sender, receiver) = channel();
+        std::thread::spawn(move || loop {
+            let line = match editor.readline("> ") {
+                Ok(line) => {
+                    let _ = editor.add_history_entry(line.as_str());
+                    line
+                },
+                Err(ReadlineError::Interrupted) => {
+                    println!("CTRL-C");
+                    "cancel".to_string()
+                },
+                Err(ReadlineError::Eof) => {
+                    // The input is closed, so there is nothing more to read
+                    println!("CTRL-D");
+                    let _ = sender.send("exit".to_string());
+                    return;
+                },
+                Err(err) => {
+                    println!("Error: {:?}", err);
+                    return;
+                }
+            };
+            if sender.send(line).is_err() {
+                return;
+            }
+        });
+
+        Ok(RustylineFrontend {
+            input: Mutex::new(receiver),
+            printer: Mutex::new(Box::new(printer)),
+            completions,
+        })
+    }
+}
+
+impl DevConsoleFrontend for RustylineFrontend {
+    fn read_input(&mut self) -> Vec<String> {
+        self.input.get_mut().unwrap().try_iter().collect()
+    }
+
+    fn write_output(&mut self, line: &str) {
+        let _ = self.printer.get_mut().unwrap().print(format!("{}\n", line));
+    }
+
+    fn report_error(&mut self, error: &str) {
+        let _ = self.printer.get_mut().unwrap().print(format!("error: {}\n", error));
+    }
+
+    fn update_completions(&mut self, completions: &CompletionTree) {
+        *self.completions.lock().unwrap() = completions.clone();
+    }
+}
@@ -1,10 +1,12 @@
 use std::str::FromStr;
 
 use crate::dev_api::*;
+use crate::jobs::DevJobs;
 use bevy::prelude::*;
 
-#[derive(Resource, Default)]
-pub struct Gold(u64);
+#[derive(Resource, Reflect, Default)]
+#[reflect(Resource)]
+pub struct Gold(pub u64);
 
 /// Sets the player's gold to the provided value.
 #[derive(Reflect, Debug, Default)]
@@ -25,6 +27,20 @@ impl DevCommand for SetGold {
     fn short_description() -> Option<&'static str> {
         Some("Sets the player's gold to the provided value.")
     }
+
+    fn runs_on() -> RunsOn {
+        RunsOn::Server
+    }
+
+    fn arguments() -> Vec<(&'static str, ArgRequirement)> {
+        vec![("amount", ArgRequirement::Required)]
+    }
+}
+
+impl ReversibleDevCommand for SetGold {
+    fn inverse(&self, world: &World) -> Self {
+        SetGold { amount: world.resource::<Gold>().0 }
+    }
 }
 
 impl FromStr for SetGold {
@@ -48,6 +64,56 @@ impl FromStr for SetGold {
     }
 }
 
+/// Adds the provided value to the player's gold.
+#[derive(Reflect, Debug, Default)]
+pub struct AddGold {
+    pub amount: u64,
+}
+
+impl bevy::ecs::world::Command for AddGold {
+    fn apply(self, world: &mut World) {
+        let mut current_gold = world.resource_mut::<Gold>();
+        current_gold.0 = current_gold.0.saturating_add(self.amount);
+
+        info!("Added {} gold, now {}", self.amount, current_gold.0);
+    }
+}
+
+impl DevCommand for AddGold {
+    fn short_description() -> Option<&'static str> {
+        Some("Adds the provided value to the player's gold.")
+    }
+
+    fn runs_on() -> RunsOn {
+        RunsOn::Server
+    }
+
+    fn arguments() -> Vec<(&'static str, ArgRequirement)> {
+        vec![("amount", ArgRequirement::Required)]
+    }
+}
+
+impl FromStr for AddGold {
+    type Err = DevToolParseError;
+    fn from_str(s: &str) -> Result<Self, DevToolParseError>{
+        let mut parts = s.split_whitespace();
+        //return error if name if none
+        let Some(name) = parts.next() else {
+            return Err(DevToolParseError::InvalidName);
+        };
+        if name != Self::name() {
+            return Err(DevToolParseError::InvalidName);
+        }
+
+        let Some(amount_string) = parts.next() else {
+            return Err(DevToolParseError::InvalidToolData);
+        };
+        let amount = amount_string.parse().map_err(|_| DevToolParseError::InvalidToolData)?;
+
+        Ok(AddGold {amount} )
+    }
+}
+
 /// Sets the player's gold to the provided value.
 #[derive(Reflect, Debug, Default)]
 pub struct PrintGold;
@@ -63,6 +129,14 @@ impl DevCommand for PrintGold {
     fn short_description() -> Option<&'static str> {
         Some("Prints the player's gold.")
     }
+
+    fn permission() -> PermissionLevel {
+        PermissionLevel::ReadOnly
+    }
+
+    fn runs_on() -> RunsOn {
+        RunsOn::Server
+    }
 }
 
 impl FromStr for PrintGold {
@@ -80,3 +154,160 @@ impl FromStr for PrintGold {
         Ok(PrintGold)
     }
 }
+
+/// Spawns empty entities over several frames, to see how spawning affects the frame time.
+#[derive(Reflect, Debug)]
+pub struct BenchmarkSpawn {
+    pub count: u64,
+    pub frames: u64,
+}
+
+impl Default for BenchmarkSpawn {
+    fn default() -> Self {
+        BenchmarkSpawn { count: 10_000, frames: 60 }
+    }
+}
+
+impl bevy::ecs::world::Command for BenchmarkSpawn {
+    fn apply(self, world: &mut World) {
+        let per_frame = self.count.div_ceil(self.frames.max(1));
+        let name = format!("spawn {} entities over {} frames", self.count, self.frames);
+        world.get_resource_or_insert_with(DevJobs::default).start(name, SpawnJob {
+            spawned: Vec::new(),
+            count: self.count,
+            per_frame,
+        });
+    }
+}
+
+impl DevCommand for BenchmarkSpawn {
+    fn short_description() -> Option<&'static str> {
+        Some("Spawns empty entities over several frames.")
+    }
+}
+
+impl FromStr for BenchmarkSpawn {
+    type Err = DevToolParseError;
+    fn from_str(s: &str) -> Result<Self, DevToolParseError>{
+        let mut parts = s.split_whitespace();
+        //return error if name if none
+        let Some(name) = parts.next() else {
+            return Err(DevToolParseError::InvalidName);
+        };
+        if name != Self::name() {
+            return Err(DevToolParseError::InvalidName);
+        }
+
+        let mut command = BenchmarkSpawn::default();
+        if let Some(count) = parts.next() {
+            command.count = count.parse().map_err(|_| DevToolParseError::InvalidToolData)?;
+        }
+        if let Some(frames) = parts.next() {
+            command.frames = frames.parse().map_err(|_| DevToolParseError::InvalidToolData)?;
+        }
+        Ok(command)
+    }
+}
+
+/// The job started by [`BenchmarkSpawn`].
+struct SpawnJob {
+    spawned: Vec<Entity>,
+    count: u64,
+    per_frame: u64,
+}
+
+impl DevJob for SpawnJob {
+    fn poll(&mut self, world: &mut World) -> JobStatus {
+        let batch = self.per_frame.min(self.count - self.spawned.len() as u64);
+        for _ in 0..batch {
+            self.spawned.push(world.spawn_empty().id());
+        }
+        if self.spawned.len() as u64 >= self.count {
+            JobStatus::Done
+        } else {
+            JobStatus::Running(Some(self.spawned.len() as f32 / self.count as f32))
+        }
+    }
+
+    fn cancel(&mut self, world: &mut World) {
+        // Leave the world as it was before the benchmark
+        for entity in self.spawned.drain(..) {
+            world.despawn(entity);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::DevCommandFailed;
+    use crate::harness::DevConsoleTestHarness;
+
+    fn harness() -> DevConsoleTestHarness {
+        let mut harness = DevConsoleTestHarness::new();
+        harness.insert_resource(Gold::default());
+        harness.toolbox_mut().direct_applyer::<u64, _>();
+        harness.add_reversible_command_at::<SetGold>("gold set").unwrap();
+        harness.add_command_at::<AddGold>("gold add").unwrap();
+        harness.add_command_at::<PrintGold>("gold print").unwrap();
+        harness
+    }
+
+    #[test]
+    fn gold_set() {
+        let mut harness = harness();
+        let output = harness.run("gold set 50");
+        assert!(output.is_ok(), "{:?}", output);
+        assert!(output.contains("Set gold to 50"));
+        assert_eq!(harness.world().resource::<Gold>().0, 50);
+
+        let output = harness.run("gold add 5");
+        assert!(output.contains("Added 5 gold, now 55"), "{:?}", output);
+        assert_eq!(harness.world().resource::<Gold>().0, 55);
+
+        assert!(harness.run("undo").is_ok());
+        assert_eq!(harness.world().resource::<Gold>().0, 0);
+    }
+
+    #[test]
+    fn gold_print() {
+        let mut harness = harness();
+        harness.world_mut().resource_mut::<Gold>().0 = 12;
+        let output = harness.run_as("gold print", PermissionLevel::ReadOnly);
+        assert!(output.is_ok(), "{:?}", output);
+        assert_eq!(output.output.last().map(String::as_str), Some("Gold: 12"));
+    }
+
+    #[test]
+    fn missing_arguments() {
+        let mut harness = harness();
+        let output = harness.run("gold set");
+        assert!(output.has_error("Missing required arguments: amount"), "{:?}", output);
+
+        let events = harness.world().resource::<Events<DevCommandFailed>>();
+        let errors = events.get_reader().read(events).map(|event| event.error.clone()).collect::<Vec<_>>();
+        assert!(matches!(errors.as_slice(), [DevToolParseError::MissingArguments(missing)] if *missing == ["amount"]));
+        assert_eq!(harness.world().resource::<Gold>().0, 0);
+    }
+
+    #[test]
+    fn unknown_commands_suggest_close_names() {
+        let mut harness = harness();
+        let output = harness.run("gold sett 5");
+        assert!(output.has_error("unknown command 'gold sett'; did you mean 'gold set'?"), "{:?}", output);
+
+        let output = harness.run("golds");
+        assert!(output.has_error("unknown command 'golds'; did you mean 'gold'?"), "{:?}", output);
+
+        let events = harness.world().resource::<Events<DevCommandFailed>>();
+        let suggestions = events
+            .get_reader()
+            .read(events)
+            .filter_map(|event| match &event.error {
+                DevToolParseError::UnknownCommand { suggestions, .. } => Some(suggestions.clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(suggestions, vec![vec!["gold set".to_string()], vec!["gold".to_string()]]);
+    }
+}
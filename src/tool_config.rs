@@ -0,0 +1,327 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use bevy::app::AppExit;
+use bevy::ecs::component::Tick;
+use bevy::prelude::*;
+use bevy::reflect::serde::TypedReflectSerializer;
+use bevy::reflect::ReflectRef;
+use bevy::scene::ron;
+use bevy::utils::HashMap;
+use serde_json::{Map, Value};
+
+use crate::keybinds::Keybindings;
+use crate::presets::ToolPresets;
+use crate::{get_field_by_name_readonly, CLIToolBox};
+
+/// Keeps the configuration of every modal dev tool, the [`ToolPresets`] saved from them and the [`Keybindings`], between runs.
+///
+/// The reflected state of each registered tool, keyed by the qualified name of the tool, its saved presets
+/// and the bound chords are saved to a RON file [`SAVE_DELAY`] after a tool or a binding changes, and when the app exits.
+/// The file is loaded once the tools are registered at startup, so they must be registered by a `Startup`
+/// system. Fields that no longer exist are skipped with a warning. Saved bindings replace the ones bound
+/// in code to the same chords.
+///
+/// `tools reset <tool>` sets a tool back to its `Default`, which is saved like any other change.
+pub struct ToolConfigPlugin {
+    pub path: PathBuf,
+}
+
+impl Default for ToolConfigPlugin {
+    fn default() -> Self {
+        ToolConfigPlugin {
+            path: PathBuf::from("dev_tools.ron"),
+        }
+    }
+}
+
+impl Plugin for ToolConfigPlugin {
+    fn build(&self, app: &mut App) {
+        if !cfg!(feature = "dev_tools") {
+            return;
+        }
+        app.insert_resource(ToolConfig {
+            path: self.path.clone(),
+            saved: String::new(),
+            last_check: Tick::new(0),
+            changed_at: None,
+        })
+        .add_systems(PostStartup, load_tool_config)
+        .add_systems(Last, save_tool_config);
+    }
+}
+
+/// Resource tracking the tool configuration file.
+#[derive(Resource)]
+pub struct ToolConfig {
+    pub path: PathBuf,
+    /// The contents of the file as last read or written, so unchanged configurations are not written again.
+    saved: String,
+    /// The change tick the tools were last checked at.
+    last_check: Tick,
+    /// When the tools first changed since they were last saved, in the real time elapsed since startup.
+    changed_at: Option<Duration>,
+}
+
+/// How long the changes to the tools are collected before they are saved, so that tools changed
+/// every frame are not serialized every frame.
+pub const SAVE_DELAY: Duration = Duration::from_secs(1);
+
+impl CLIToolBox {
+    /// The reflected state of every registered tool, by qualified name.
+    pub fn tool_config(&self, world: &World) -> Value {
+        let mut tools = Map::new();
+        for qualified_name in self.tool_metadatas.keys() {
+            let tool = (self.get_tool_fn[qualified_name])(world);
+            match serde_json::to_value(TypedReflectSerializer::new(tool, &self.type_registry)) {
+                Ok(value) => {
+                    tools.insert(qualified_name.clone(), value);
+                },
+                Err(err) => error!("Failed to save the configuration of {}: {}", qualified_name, err),
+            }
+        }
+        Value::Object(tools)
+    }
+
+    /// Apply a configuration created by [`tool_config`](Self::tool_config) to the registered tools.
+    pub fn apply_tool_config(&self, config: &Value, world: &mut World) {
+        let Value::Object(tools) = config else {
+            error!("The tool configuration must be a map of tool names to their fields");
+            return;
+        };
+        for (qualified_name, fields) in tools.iter() {
+            let (Some(insert_fn), Value::Object(fields)) = (self.tool_insert_fn.get(qualified_name), fields) else {
+                warn!("Skipping the configuration of unknown tool {}", qualified_name);
+                continue;
+            };
+
+            let mut patch = HashMap::new();
+            {
+                let tool = (self.get_tool_fn[qualified_name])(world);
+                for (field_name, value) in fields.iter() {
+                    let field = match get_field_by_name_readonly(tool, field_name) {
+                        Ok(field) => field,
+                        Err(err) => {
+                            warn!("Skipping {} of {}: {}", field_name, qualified_name, err);
+                            continue;
+                        },
+                    };
+                    match self.deserialize_field(field, value) {
+                        Ok(value) => {
+                            patch.insert(field_name.clone(), value);
+                        },
+                        Err(err) => warn!("Skipping {} of {}: {}", field_name, qualified_name, err),
+                    }
+                }
+            }
+
+            if let Err(err) = insert_fn(world, patch) {
+                error!("Failed to load the configuration of {}: {}", qualified_name, err);
+            }
+        }
+    }
+
+    /// Handle the `tools` builtin.
+    pub fn tools_command(&self, args: &[&str], world: &mut World) {
+        let ["reset", name] = args else {
+            error!("Usage: tools reset <tool>");
+            return;
+        };
+        let qualified_name = match self.find_tool(name) {
+            Ok(Some(qualified_name)) => qualified_name,
+            Ok(None) => {
+                error!("{}", self.unknown_command_error(name));
+                return;
+            },
+            Err(_) => return,
+        };
+
+        let default = (self.tool_metadatas[&qualified_name].create_default_fn)();
+        let ReflectRef::Struct(default) = default.reflect_ref() else {
+            error!("Only tools with named fields can be reset");
+            return;
+        };
+        let patch = (0..default.field_len())
+            .filter_map(|idx| Some((default.name_at(idx)?.to_string(), default.field_at(idx)?.clone_value())))
+            .collect::<HashMap<_, _>>();
+
+        match self.patch_tool(&qualified_name, patch, world) {
+            Ok(()) => info!("Reset {} to its defaults", name),
+            Err(err) => error!("Failed to reset {}: {}", name, err),
+        }
+    }
+}
+
+fn load_tool_config(world: &mut World) {
+    let path = world.resource::<ToolConfig>().path.clone();
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        // Nothing was saved yet
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return,
+        Err(err) => {
+            error!("Failed to read the tool configuration {}: {}", path.display(), err);
+            return;
+        },
+    };
+    let config = match ron::from_str::<Value>(&contents) {
+        Ok(config) => config,
+        Err(err) => {
+            error!("Failed to parse the tool configuration {}: {}", path.display(), err);
+            return;
+        },
+    };
+
+    if let Some(tools) = config.get("tools") {
+        world.resource_scope(|world, toolbox: Mut<CLIToolBox>| toolbox.apply_tool_config(tools, world));
+    }
+    if let Some(presets) = config.get("presets") {
+        world.resource_scope(|world, toolbox: Mut<CLIToolBox>| toolbox.apply_preset_config(presets, world));
+    }
+    if let Some(Value::Object(binds)) = config.get("binds") {
+        let mut keybindings = world.get_resource_or_insert_with(Keybindings::default);
+        for (chord, line) in binds.iter() {
+            if let Some(line) = line.as_str() {
+                let _ = keybindings.bind(chord, line);
+            }
+        }
+    }
+    info!("Loaded the tool configuration from {}", path.display());
+
+    // The tools now match the file, so there is nothing to save until they change again
+    let change_tick = world.read_change_tick();
+    let mut tool_config = world.resource_mut::<ToolConfig>();
+    tool_config.saved = contents;
+    tool_config.last_check = change_tick;
+}
+
+/// Save the tool configuration [`SAVE_DELAY`] after a tool or a binding changed, or when the app exits.
+fn save_tool_config(world: &mut World) {
+    let change_tick = world.read_change_tick();
+    let last_check = world.resource::<ToolConfig>().last_check;
+    let changed = world.resource_scope(|world, toolbox: Mut<CLIToolBox>| {
+        toolbox.tool_metadatas
+            .values()
+            .map(|metadata| metadata.type_id)
+            .chain([std::any::TypeId::of::<Keybindings>(), std::any::TypeId::of::<ToolPresets>()])
+            .any(|type_id| {
+                world.components()
+                    .get_resource_id(type_id)
+                    .and_then(|id| world.get_resource_change_ticks_by_id(id))
+                    .is_some_and(|ticks| ticks.is_changed(last_check, change_tick))
+            })
+    });
+    let exiting = world.get_resource::<Events<AppExit>>().is_some_and(|events| !events.is_empty());
+    let now = world.resource::<Time<Real>>().elapsed();
+
+    let mut tool_config = world.resource_mut::<ToolConfig>();
+    tool_config.last_check = change_tick;
+    if changed && tool_config.changed_at.is_none() {
+        tool_config.changed_at = Some(now);
+    }
+    let Some(changed_at) = tool_config.changed_at else {
+        return;
+    };
+    if now - changed_at < SAVE_DELAY && !exiting {
+        return;
+    }
+    tool_config.changed_at = None;
+
+    // Serializing every tool is only worth it once the changes are collected
+    let contents = world.resource_scope(|world, toolbox: Mut<CLIToolBox>| {
+        let binds = world.get_resource::<Keybindings>().map_or(Map::new(), |keybindings| {
+            keybindings.iter().map(|(chord, line)| (chord, Value::from(line))).collect()
+        });
+        let presets = world.get_resource::<ToolPresets>().map_or(Value::Object(Map::new()), |presets| toolbox.preset_config(presets));
+        let config = serde_json::json!({ "tools": toolbox.tool_config(world), "presets": presets, "binds": binds });
+        ron::ser::to_string_pretty(&config, ron::ser::PrettyConfig::default()).ok()
+    });
+
+    let mut tool_config = world.resource_mut::<ToolConfig>();
+    let Some(contents) = contents.filter(|contents| *contents != tool_config.saved) else {
+        return;
+    };
+    if let Err(err) = std::fs::write(&tool_config.path, &contents) {
+        error!("Failed to save the tool configuration {}: {}", tool_config.path.display(), err);
+    }
+    tool_config.saved = contents;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use bevy::time::TimeUpdateStrategy;
+    use serde_json::json;
+
+    use super::*;
+    use crate::dev_api::ModalDevTool;
+    use crate::harness::DevConsoleTestHarness;
+    use crate::test_tool::DevFlyCamera;
+
+    fn harness(path: &Path) -> DevConsoleTestHarness {
+        let mut harness = DevConsoleTestHarness::new();
+        harness.insert_resource(DevFlyCamera::default());
+        harness.add_tool::<DevFlyCamera>().unwrap();
+        harness.app.add_plugins(ToolConfigPlugin { path: path.to_path_buf() });
+        // Each frame takes half of the delay
+        harness.insert_resource(TimeUpdateStrategy::ManualDuration(SAVE_DELAY / 2));
+        harness
+    }
+
+    /// A path in a new temporary directory, removed with its contents when dropped.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("{}_{}", name, std::process::id()));
+            std::fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn changes_are_saved_after_the_delay() {
+        let dir = TempDir::new("tool_config_delay");
+        let path = dir.0.join("dev_tools.ron");
+        let mut harness = harness(&path);
+
+        harness.world_mut().resource_mut::<DevFlyCamera>().enabled = true;
+        harness.advance(2);
+        assert!(std::fs::metadata(&path).is_err());
+
+        harness.advance(1);
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(contents.contains("\"enabled\": true"), "{}", contents);
+    }
+
+    #[test]
+    fn changes_are_saved_on_exit() {
+        let dir = TempDir::new("tool_config_exit");
+        let path = dir.0.join("dev_tools.ron");
+        let mut harness = harness(&path);
+
+        harness.world_mut().resource_mut::<DevFlyCamera>().enabled = true;
+        harness.advance(1);
+        assert!(std::fs::metadata(&path).is_err());
+
+        harness.world_mut().send_event(AppExit);
+        harness.advance(1);
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(contents.contains("\"enabled\": true"), "{}", contents);
+    }
+
+    #[test]
+    fn unknown_fields_are_skipped() {
+        let dir = TempDir::new("tool_config_unknown");
+        let mut harness = harness(&dir.0.join("dev_tools.ron"));
+        let config = json!({ DevFlyCamera::metadata().qualified_name(): { "enabled": true, "zoom": 2.0 } });
+        harness.world_mut().resource_scope(|world, toolbox: Mut<CLIToolBox>| toolbox.apply_tool_config(&config, world));
+        assert!(harness.world().resource::<DevFlyCamera>().enabled);
+    }
+}